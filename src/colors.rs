@@ -0,0 +1,45 @@
+///
+/// A plain RGB color, so the game can pick colors for things without depending on tcod.
+/// The UI turns these into tcod colors when drawing.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+}
+
+//The named colors in use, with the same values as libtcod's
+pub const BLACK: Color = Color::new(0, 0, 0);
+pub const LIGHT_GREY: Color = Color::new(159, 159, 159);
+pub const WHITE: Color = Color::new(255, 255, 255);
+pub const SEPIA: Color = Color::new(127, 101, 63);
+
+pub const RED: Color = Color::new(255, 0, 0);
+pub const FLAME: Color = Color::new(255, 63, 0);
+pub const ORANGE: Color = Color::new(255, 127, 0);
+pub const AMBER: Color = Color::new(255, 191, 0);
+pub const YELLOW: Color = Color::new(255, 255, 0);
+pub const GREEN: Color = Color::new(0, 255, 0);
+pub const SKY: Color = Color::new(0, 191, 255);
+pub const VIOLET: Color = Color::new(127, 0, 255);
+pub const GOLD: Color = Color::new(229, 191, 0);
+
+pub const LIGHT_RED: Color = Color::new(255, 115, 115);
+pub const LIGHT_YELLOW: Color = Color::new(255, 255, 115);
+pub const LIGHT_GREEN: Color = Color::new(115, 255, 115);
+pub const LIGHT_CYAN: Color = Color::new(115, 255, 255);
+pub const LIGHT_BLUE: Color = Color::new(115, 115, 255);
+pub const LIGHT_VIOLET: Color = Color::new(185, 115, 255);
+
+pub const DARK_RED: Color = Color::new(191, 0, 0);
+pub const DARKER_RED: Color = Color::new(128, 0, 0);
+pub const DARKER_ORANGE: Color = Color::new(128, 64, 0);
+pub const DARKER_GREEN: Color = Color::new(0, 128, 0);
+pub const DESATURATED_GREEN: Color = Color::new(63, 127, 63);
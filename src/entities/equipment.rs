@@ -0,0 +1,57 @@
+use std::fmt;
+
+use super::light::LightSource;
+
+//An object that can be equipped, yielding bonuses while it is
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    pub max_hp_bonus: i32,
+    pub light: Option<LightSource>, //Light cast on the wearer while equipped
+    pub fuel: Option<i32>,          //Turns of light left. None if it never burns out
+}
+
+impl Equipment {
+    pub fn new(slot: Slot) -> Self {
+        Equipment {
+            slot,
+            equipped: false,
+            power_bonus: 0,
+            defense_bonus: 0,
+            max_hp_bonus: 0,
+            light: None,
+            fuel: None,
+        }
+    }
+
+    //The light this gives off right now, if it has any fuel left
+    pub fn lit(&self) -> Option<LightSource> {
+        if self.fuel.map_or(false, |fuel| fuel <= 0) {
+            None
+        } else {
+            self.light
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    Weapon,
+    Armor,
+    Shield,
+    Light,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Slot::Weapon => write!(f, "weapon"),
+            Slot::Armor => write!(f, "armor"),
+            Slot::Shield => write!(f, "shield"),
+            Slot::Light => write!(f, "light"),
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::cmp;
+
+use colors;
+
+use entities::object::Ai;
+use game::{GameState, PLAYER_IDX};
+
+const HEAL_AMOUNT: i32 = 4;
+const LIGHTNING_DAMAGE: i32 = 20;
+const LIGHTNING_RANGE: f32 = 5.0;
+const CONFUSE_RANGE: f32 = 8.0;
+const CONFUSE_NUM_TURNS: i32 = 10;
+const FIREBALL_RADIUS: f32 = 3.0;
+const FIREBALL_DAMAGE: i32 = 12;
+const REFUEL_AMOUNT: i32 = 300;
+
+//An object that can be picked up and used from the inventory
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Heal,
+    Lightning,
+    Confuse,
+    Fireball,
+    Refuel,
+    Equipment,
+}
+
+//What an item needs to be pointed at before it can be used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Targeting {
+    Player,
+    NearestMonster,
+    Tile { max_range: Option<f32> },
+}
+
+impl Item {
+    pub fn targeting(self) -> Targeting {
+        use self::Item::*;
+        match self {
+            Heal => Targeting::Player,
+            Lightning => Targeting::NearestMonster,
+            Confuse => Targeting::Tile {
+                max_range: Some(CONFUSE_RANGE),
+            },
+            Fireball => Targeting::Tile { max_range: None },
+            Refuel => Targeting::Player,
+            Equipment => Targeting::Player,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UseResult {
+    UsedUp,
+    UsedAndKept,
+    Cancelled,
+}
+
+///
+/// Uses the item at inventory_id. Items with tile targeting need a target
+/// the player can currently see.
+///
+pub fn use_item(
+    inventory_id: usize,
+    target: Option<(i32, i32)>,
+    game: &mut GameState,
+) -> UseResult {
+    use self::Item::*;
+    // just call the "use_function" if it is defined
+    if let Some(item) = game.inventory[inventory_id].item {
+        let on_use: fn(usize, Option<(i32, i32)>, &mut GameState) -> UseResult = match item {
+            Heal => cast_heal,
+            Lightning => cast_lightning,
+            Confuse => cast_confuse,
+            Fireball => cast_fireball,
+            Refuel => cast_refuel,
+            Equipment => toggle_equipment,
+        };
+        match on_use(inventory_id, target, game) {
+            UseResult::UsedUp => {
+                // destroy after use, unless it was cancelled for some reason
+                game.inventory.remove(inventory_id);
+                UseResult::UsedUp
+            }
+            UseResult::UsedAndKept => UseResult::UsedAndKept,
+            UseResult::Cancelled => {
+                game.messages.add("Cancelled", colors::WHITE);
+                UseResult::Cancelled
+            }
+        }
+    } else {
+        game.messages.add(
+            format!("The {} cannot be used.", game.inventory[inventory_id].name),
+            colors::WHITE,
+        );
+        UseResult::Cancelled
+    }
+}
+
+fn cast_heal(_inventory_id: usize, _target: Option<(i32, i32)>, game: &mut GameState) -> UseResult {
+    // heal the player
+    let max_hp = game.max_hp(PLAYER_IDX);
+    if let Some(fighter) = game.objects[PLAYER_IDX].fighter {
+        if fighter.hp == max_hp {
+            game.messages
+                .add("You are already at full health.", colors::RED);
+            return UseResult::Cancelled;
+        }
+        game.messages
+            .add("Your wounds start to feel better!", colors::LIGHT_VIOLET);
+        game.objects[PLAYER_IDX].heal(HEAL_AMOUNT, max_hp);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+fn cast_lightning(
+    _inventory_id: usize,
+    _target: Option<(i32, i32)>,
+    game: &mut GameState,
+) -> UseResult {
+    // find closest enemy (inside a maximum range) and damage it
+    match closest_monster(LIGHTNING_RANGE, game) {
+        Some(monster_id) => {
+            game.messages.add(
+                format!(
+                    "A lightning bolt strikes the {} with a loud thunder! \
+                     The damage is {} hit points.",
+                    game.objects[monster_id].name, LIGHTNING_DAMAGE
+                ),
+                colors::LIGHT_BLUE,
+            );
+            if let Some(xp) =
+                game.objects[monster_id].take_damage(LIGHTNING_DAMAGE, &mut game.messages)
+            {
+                if let Some(fighter) = game.objects[PLAYER_IDX].fighter.as_mut() {
+                    fighter.xp += xp;
+                }
+            }
+            UseResult::UsedUp
+        }
+        None => {
+            // no enemy found within maximum range
+            game.messages
+                .add("No enemy is close enough to strike.", colors::RED);
+            UseResult::Cancelled
+        }
+    }
+}
+
+fn cast_confuse(
+    _inventory_id: usize,
+    target: Option<(i32, i32)>,
+    game: &mut GameState,
+) -> UseResult {
+    // ask the player for a target to confuse
+    let monster_id = target
+        .filter(|&(x, y)| in_range(x, y, Some(CONFUSE_RANGE), game))
+        .and_then(|(x, y)| {
+            game.objects
+                .iter()
+                .position(|obj| obj.get_pos() == (x, y) && obj.ai.is_some())
+        });
+
+    match monster_id {
+        Some(monster_id) => {
+            // replace the monster's AI with a "confused" one; after
+            // some turns it will restore the old AI
+            let old_ai = game.objects[monster_id].ai.take().unwrap_or_else(Ai::basic);
+            game.objects[monster_id].ai = Some(Ai::Confused {
+                previous_ai: Box::new(old_ai),
+                num_turns: CONFUSE_NUM_TURNS,
+            });
+            game.messages.add(
+                format!(
+                    "The eyes of {} look vacant, as he starts to stumble around!",
+                    game.objects[monster_id].name
+                ),
+                colors::LIGHT_GREEN,
+            );
+            UseResult::UsedUp
+        }
+        None => {
+            game.messages
+                .add("There is no enemy there to confuse.", colors::RED);
+            UseResult::Cancelled
+        }
+    }
+}
+
+fn cast_fireball(
+    _inventory_id: usize,
+    target: Option<(i32, i32)>,
+    game: &mut GameState,
+) -> UseResult {
+    // ask the player for a target tile to throw a fireball at
+    let (x, y) = match target.filter(|&(x, y)| in_range(x, y, None, game)) {
+        Some(tile_pos) => tile_pos,
+        None => return UseResult::Cancelled,
+    };
+    game.messages.add(
+        format!(
+            "The fireball explodes, burning everything within {} tiles!",
+            FIREBALL_RADIUS
+        ),
+        colors::ORANGE,
+    );
+
+    let mut xp_to_gain = 0;
+    for (id, obj) in game.objects.iter_mut().enumerate() {
+        if obj.distance(x, y) <= FIREBALL_RADIUS && obj.fighter.is_some() {
+            game.messages.add(
+                format!(
+                    "The {} gets burned for {} hit points.",
+                    obj.name, FIREBALL_DAMAGE
+                ),
+                colors::ORANGE,
+            );
+            if let Some(xp) = obj.take_damage(FIREBALL_DAMAGE, &mut game.messages) {
+                // don't reward the player for burning themself!
+                if id != PLAYER_IDX {
+                    xp_to_gain += xp;
+                }
+            }
+        }
+    }
+    if let Some(fighter) = game.objects[PLAYER_IDX].fighter.as_mut() {
+        fighter.xp += xp_to_gain;
+    }
+    UseResult::UsedUp
+}
+
+fn cast_refuel(
+    _inventory_id: usize,
+    _target: Option<(i32, i32)>,
+    game: &mut GameState,
+) -> UseResult {
+    // top up the light the player is carrying, if it burns fuel at all
+    let light = game.inventory.iter_mut().find(|item| {
+        item.equipment.map_or(false, |e| {
+            e.equipped && e.light.is_some() && e.fuel.is_some()
+        })
+    });
+    match light {
+        Some(light) => {
+            if let Some(fuel) = light.equipment.as_mut().and_then(|e| e.fuel.as_mut()) {
+                *fuel += REFUEL_AMOUNT;
+            }
+            game.messages.add(
+                format!("You refill your {} with oil.", light.name),
+                colors::LIGHT_YELLOW,
+            );
+            UseResult::UsedUp
+        }
+        None => {
+            game.messages
+                .add("You have nothing lit to pour the oil into.", colors::RED);
+            UseResult::Cancelled
+        }
+    }
+}
+
+fn toggle_equipment(
+    inventory_id: usize,
+    _target: Option<(i32, i32)>,
+    game: &mut GameState,
+) -> UseResult {
+    let equipment = match game.inventory[inventory_id].equipment {
+        Some(equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        dequip(inventory_id, game);
+    } else {
+        // only one item per slot, so take off whatever is already there
+        let current = game.inventory.iter().position(|item| {
+            item.equipment
+                .map_or(false, |e| e.equipped && e.slot == equipment.slot)
+        });
+        if let Some(current) = current {
+            dequip(current, game);
+        }
+        equip(inventory_id, game);
+    }
+    UseResult::UsedAndKept
+}
+
+fn equip(inventory_id: usize, game: &mut GameState) {
+    let item = &mut game.inventory[inventory_id];
+    if let Some(equipment) = item.equipment.as_mut() {
+        if !equipment.equipped {
+            equipment.equipped = true;
+            game.messages.add(
+                format!("Equipped {} on {}.", item.name, equipment.slot),
+                colors::LIGHT_GREEN,
+            );
+        }
+    }
+}
+
+//Taking off something that raised max hp can leave the player with more hp than the new maximum
+pub fn dequip(inventory_id: usize, game: &mut GameState) {
+    {
+        let item = &mut game.inventory[inventory_id];
+        if let Some(equipment) = item.equipment.as_mut() {
+            if equipment.equipped {
+                equipment.equipped = false;
+                game.messages.add(
+                    format!("Dequipped {} from {}.", item.name, equipment.slot),
+                    colors::LIGHT_YELLOW,
+                );
+            }
+        }
+    }
+    let max_hp = game.max_hp(PLAYER_IDX);
+    if let Some(fighter) = game.objects[PLAYER_IDX].fighter.as_mut() {
+        fighter.hp = cmp::min(fighter.hp, max_hp);
+    }
+}
+
+/// find closest enemy, up to a maximum range, and in the player's FOV
+fn closest_monster(max_range: f32, game: &GameState) -> Option<usize> {
+    let mut closest_enemy = None;
+    let mut closest_dist = max_range; // start with the maximum range, which can still be hit
+
+    for (id, object) in game.objects.iter().enumerate() {
+        if (id != PLAYER_IDX)
+            && object.fighter.is_some()
+            && object.ai.is_some()
+            && game.fov.is_in_fov(object)
+        {
+            // calculate distance between this object and the player
+            let dist = game.objects[PLAYER_IDX].distance_to(object);
+            if dist <= closest_dist {
+                // it's closer, so remember it
+                closest_enemy = Some(id);
+                closest_dist = dist;
+            }
+        }
+    }
+    closest_enemy
+}
+
+//A tile can only be targeted if the player can see it, and it's close enough
+pub fn in_range(x: i32, y: i32, max_range: Option<f32>, game: &GameState) -> bool {
+    if !game.map.valid_point(x, y) {
+        return false;
+    }
+    game.fov.is_visible(x, y)
+        && max_range.map_or(true, |range| {
+            game.objects[PLAYER_IDX].distance(x, y) <= range
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //The player in the corner of an empty room, with a monster at (x, y)
+    fn room_with_monster(x: i32, y: i32) -> GameState {
+        let room = [
+            "##########",
+            "#........#",
+            "#........#",
+            "#........#",
+            "##########",
+        ];
+        GameState::on_fixture(&room, (1, 1), &[(x, y)])
+    }
+
+    #[test]
+    fn lightning_reaches_no_further_than_its_range() {
+        assert_eq!(
+            closest_monster(LIGHTNING_RANGE, &room_with_monster(6, 1)),
+            Some(1)
+        );
+        // sqrt(26) away, just out of range
+        assert_eq!(
+            closest_monster(LIGHTNING_RANGE, &room_with_monster(6, 2)),
+            None
+        );
+    }
+}
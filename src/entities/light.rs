@@ -1,16 +1,43 @@
 //A light source. Can be anything such as torch, fire, flashlight
-//Illumination is calculated as:
-//  100% if distance is below falloff distance
-//   1/((fall_off_distance - distance)^2)
-//Color is determined by (rgb) * (1- illumination)
+//A light's strength on a tile is calculated as:
+//  MAX_SHADE if distance is below fall_off_distance
+//  MAX_SHADE - (distance - fall_off_distance) / intensity past it, down to 0
+//A light with an angle below 360 is a cone, pointed along its owner's facing
+//Each tile gathers an Illumination, starting from the ambient light. Every light reaching it adds
+//its strength to it, channel by channel, tinted by the light's color
+//Color is determined by Illumination::tint, which scales each channel of (rgb) by the light on it
+//Animated lights change brightness over time, following a clock that ticks every turn
+
+use std::f32::consts::PI;
+
+use colors::{self, Color};
 
 const MAX_SHADE: f32 = 0.8;
+
+//A warm, yellowish flame
+pub const TORCH_COLOR: Color = Color {
+    r: 255,
+    g: 200,
+    b: 120,
+};
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LightSource {
     pub intensity: f32,         //Brightness
     pub fall_off_distance: f32, //Amount of tiles light illuminates before starting to drop off
     //pub fade_factor: f32,       //How sharply the color drops off
-    pub angle: i32,    //For now assume 360
+    pub angle: i32,    //Width of the beam in degrees. 360 lights all around
     pub max_dist: f32, //Max distance light can cover. A field calculated once on new
+    pub color: Color,
+    pub animation: Animation,
+}
+
+//How a light's brightness changes over time
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Animation {
+    Steady,
+    Flicker,                      //Random dips in brightness, like a flame
+    Pulse { period: u32 },        //Smoothly brightens and dims, once every period turns
+    Strobe { on: u32, off: u32 }, //Fully lit for on turns, then dark for off turns
 }
 
 impl LightSource {
@@ -20,9 +47,74 @@ impl LightSource {
             fall_off_distance,
             intensity,
             max_dist: (MAX_SHADE * intensity + fall_off_distance).ceil() as f32,
+            color: colors::WHITE,
+            animation: Animation::Steady,
         };
     }
 
+    pub fn cone(fall_off_distance: f32, intensity: f32, angle: i32) -> Self {
+        LightSource {
+            angle,
+            ..LightSource::new(fall_off_distance, intensity)
+        }
+    }
+
+    //Whether the tile at (dx, dy) from the light is inside the beam, when pointed along facing
+    pub fn illuminates(&self, facing: (i32, i32), dx: i32, dy: i32) -> bool {
+        if self.angle >= 360 || (dx, dy) == (0, 0) || facing == (0, 0) {
+            return true;
+        }
+        let beam = (facing.1 as f32).atan2(facing.0 as f32);
+        let tile = (dy as f32).atan2(dx as f32);
+        let mut difference = (tile - beam).abs();
+        if difference > PI {
+            difference = 2.0 * PI - difference;
+        }
+        difference.to_degrees() <= self.angle as f32 / 2.0
+    }
+
+    ///
+    /// The light as it shines at the given clock tick, or None if it is dark right now.
+    /// salt keeps lights that share an animation from changing in lockstep.
+    ///
+    pub fn at(&self, clock: u32, salt: u32) -> Option<LightSource> {
+        match self.animation {
+            Animation::Steady => Some(*self),
+            Animation::Flicker => {
+                let noise = hash_noise(clock, salt);
+                Some(self.scaled(0.8 + 0.2 * noise))
+            }
+            Animation::Pulse { period } => {
+                let phase =
+                    (clock.wrapping_add(salt) % period.max(1)) as f32 / period.max(1) as f32;
+                Some(self.scaled(0.75 + 0.25 * (phase * 2.0 * PI).sin()))
+            }
+            Animation::Strobe { on, off } => {
+                if clock.wrapping_add(salt) % (on + off).max(1) < on {
+                    Some(*self)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    //The same light with its reach scaled
+    fn scaled(&self, factor: f32) -> LightSource {
+        LightSource {
+            intensity: self.intensity * factor,
+            fall_off_distance: self.fall_off_distance * factor,
+            max_dist: (MAX_SHADE * self.intensity * factor + self.fall_off_distance * factor)
+                .ceil(),
+            ..*self
+        }
+    }
+
+    //How much brighter than the unlit darkness this light makes a tile at the given distance
+    pub fn strength(&self, distance: f32) -> f32 {
+        (MAX_SHADE - self.calc_shade_percent(distance)).max(0.0)
+    }
+
     //Number between 0, and 1 that calcs the percent of shade that covers the tile.
     pub fn calc_shade_percent(&self, distance: f32) -> f32 {
         if distance <= self.fall_off_distance {
@@ -32,3 +124,52 @@ impl LightSource {
         }
     }
 }
+
+//A number between 0 and 1 that looks random, but is always the same for the same inputs
+fn hash_noise(clock: u32, salt: u32) -> f32 {
+    let mut hash = clock.wrapping_mul(0x9E37_79B9) ^ salt.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    (hash % 1000) as f32 / 1000.0
+}
+
+//The light gathered on a tile, per channel. 1.0 shows a color at its full brightness
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Illumination {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Illumination {
+    pub fn grey(level: f32) -> Self {
+        Illumination {
+            r: level,
+            g: level,
+            b: level,
+        }
+    }
+
+    //Blend in a light of the given color, additively
+    pub fn add(&mut self, color: Color, strength: f32) {
+        self.r += strength * color.r as f32 / 255.0;
+        self.g += strength * color.g as f32 / 255.0;
+        self.b += strength * color.b as f32 / 255.0;
+    }
+
+    //Overall light level, from 0 (pitch black) to 1 (fully lit)
+    pub fn brightness(&self) -> f32 {
+        ((self.r + self.g + self.b) / 3.0).min(1.0)
+    }
+
+    //Shows a color as it looks under this light
+    pub fn tint(&self, color: Color) -> Color {
+        let channel = |value: u8, light: f32| (value as f32 * light.min(1.0)) as u8;
+        Color {
+            r: channel(color.r, self.r),
+            g: channel(color.g, self.g),
+            b: channel(color.b, self.b),
+        }
+    }
+}
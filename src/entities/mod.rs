@@ -1,4 +1,6 @@
 extern crate rand;
 
+pub mod equipment;
+pub mod item;
 pub mod light;
 pub mod object;
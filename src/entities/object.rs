@@ -1,12 +1,13 @@
 extern crate rand;
-extern crate tcod;
 
-use tcod::colors::{self, Color};
-use tcod::console::*;
+use colors::{self, Color};
 
+use super::equipment::Equipment;
+use super::item::Item;
 use super::light::LightSource;
-use Game;
+use ui::messages::Messages;
 
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Object {
     x: i32,
     y: i32,
@@ -15,9 +16,16 @@ pub struct Object {
     pub name: String,
     pub blocks: bool,
     pub alive: bool,
+    pub always_visible: bool,
+    pub opens_doors: bool, //Bumping into a closed door opens it
+    pub stairs: bool,      //Taking these leads down to the next level
+    pub level: i32,
+    pub facing: (i32, i32), //Direction of the last step taken, which directional lights point along
     pub fighter: Option<Fighter>,
     pub ai: Option<Ai>,
     pub light: Option<LightSource>,
+    pub item: Option<Item>,
+    pub equipment: Option<Equipment>,
 }
 
 impl Object {
@@ -30,21 +38,19 @@ impl Object {
             name: name.into(),
             blocks,
             alive: false,
+            always_visible: false,
+            opens_doors: false,
+            stairs: false,
+            level: 1,
+            facing: (0, 1),
             fighter: None,
             ai: None,
             light: None,
+            item: None,
+            equipment: None,
         }
     }
 
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.color);
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
-    }
-
-    pub fn clear(&self, con: &mut dyn Console) {
-        con.put_char(self.x, self.y, ' ', BackgroundFlag::None);
-    }
-
     pub fn get_pos(&self) -> (i32, i32) {
         return (self.x, self.y);
     }
@@ -54,13 +60,26 @@ impl Object {
         self.y = y;
     }
 
+    //Turns to face along a step. Standing still keeps the current facing
+    pub fn face(&mut self, dx: i32, dy: i32) {
+        if (dx, dy) != (0, 0) {
+            self.facing = (dx.signum(), dy.signum());
+        }
+    }
+
     pub fn distance_to(&self, other: &Object) -> f32 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
+        return self.distance(other.x, other.y);
+    }
+
+    /// return the distance to some coordinates
+    pub fn distance(&self, x: i32, y: i32) -> f32 {
+        let dx = x - self.x;
+        let dy = y - self.y;
         return ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
     }
 
-    pub fn take_damage(&mut self, damage: i32, game: &mut Game) {
+    /// Returns the experience gained for killing this object, if the damage killed it
+    pub fn take_damage(&mut self, damage: i32, messages: &mut Messages) -> Option<i32> {
         if let Some(fighter) = self.fighter.as_mut() {
             if damage > 0 {
                 fighter.hp -= damage
@@ -70,25 +89,42 @@ impl Object {
         if let Some(fighter) = self.fighter {
             if fighter.hp <= 0 {
                 self.alive = false;
-                fighter.on_death.callback(self, game);
+                fighter.on_death.callback(self, messages);
+                return Some(fighter.xp);
+            }
+        }
+        None
+    }
+
+    /// heal by the given amount, without going over the (effective) maximum
+    pub fn heal(&mut self, amount: i32, max_hp: i32) {
+        if let Some(ref mut fighter) = self.fighter {
+            fighter.hp += amount;
+            if fighter.hp > max_hp {
+                fighter.hp = max_hp;
             }
         }
     }
 
-    pub fn attack(&mut self, target: &mut Object, game: &mut Game) {
-        let damage = self.fighter.map_or(0, |f| f.power) - target.fighter.map_or(0, |f| f.defense);
+    /// damage is worked out by the caller, from the effective power and defense of both sides
+    pub fn attack(&mut self, target: &mut Object, damage: i32, messages: &mut Messages) {
         if damage > 0 {
             // make the target take some damage
-            game.messages.add(
+            messages.add(
                 format!(
                     "{} attacks {} for {} hit points.",
                     self.name, target.name, damage
                 ),
                 colors::WHITE,
             );
-            target.take_damage(damage, game);
+            if let Some(xp) = target.take_damage(damage, messages) {
+                // yield experience to the attacker
+                if let Some(fighter) = self.fighter.as_mut() {
+                    fighter.xp += xp;
+                }
+            }
         } else {
-            game.messages.add(
+            messages.add(
                 format!(
                     "{} attacks {} but it has no effect!",
                     self.name, target.name
@@ -100,51 +136,76 @@ impl Object {
 }
 
 // combat-related properties and methods (monster, player, NPC).
-#[derive(Clone, Copy, Debug, PartialEq)]
+// max_hp, defense and power are base values, before any equipment bonuses
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Fighter {
     pub max_hp: i32,
     pub hp: i32,
     pub defense: i32,
     pub power: i32,
+    pub xp: i32, //Experience carried, for the player. Experience granted on death, for monsters
     pub on_death: DeathCallback,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Ai {
-    Basic,
+    Basic {
+        awareness: Awareness,
+    },
+    Confused {
+        previous_ai: Box<Ai>,
+        num_turns: i32,
+    },
+}
+
+impl Ai {
+    // a monster that hasn't noticed anything yet
+    pub fn basic() -> Self {
+        Ai::Basic {
+            awareness: Awareness::Unaware,
+        }
+    }
+}
+
+// what a monster knows about where its prey is. Positions are where it was last seen
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Awareness {
+    Unaware,
+    Suspicious { x: i32, y: i32, turns: i32 }, // caught a glimpse, and is going to look
+    Hunting { x: i32, y: i32 },
+    LostTrack { turns: i32 }, // got to the last known position and found nothing, searching around
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum DeathCallback {
     Player,
     Monster,
 }
 
 impl DeathCallback {
-    pub fn callback(self, object: &mut Object, game: &mut Game) {
+    pub fn callback(self, object: &mut Object, messages: &mut Messages) {
         use self::DeathCallback::*;
-        let callback: fn(&mut Object, &mut Game) = match self {
+        let callback: fn(&mut Object, &mut Messages) = match self {
             Player => player_death,
             Monster => monster_death,
         };
-        callback(object, game);
+        callback(object, messages);
     }
 }
 
-fn player_death(player: &mut Object, game: &mut Game) {
+fn player_death(player: &mut Object, messages: &mut Messages) {
     // the game ended!
-    game.messages.add("You Died!", colors::RED);
+    messages.add("You Died!", colors::RED);
 
     // for added effect, transform the player into a corpse!
     player.char = '%';
     player.color = colors::DARK_RED;
 }
 
-fn monster_death(monster: &mut Object, game: &mut Game) {
+fn monster_death(monster: &mut Object, messages: &mut Messages) {
     // transform it into a nasty corpse! it doesn't block, can't be
     // attacked and doesn't move
-    game.messages
-        .add(format!("{} is dead!", monster.name), colors::ORANGE);
+    messages.add(format!("{} is dead!", monster.name), colors::ORANGE);
     monster.char = '%';
     monster.color = colors::DARK_RED;
     monster.blocks = false;
@@ -1,7 +1,1177 @@
-use map::map::Map;
+use std::cmp::{self, Ordering};
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+
+use colors;
+
+use entities::equipment::Equipment;
+use entities::item::{self, UseResult};
+use entities::light::{Illumination, LightSource};
+use entities::object::{Ai, Awareness, DeathCallback, Fighter, Object};
+use map::dijkstra::walk_cost;
+use map::fov::FovAlgorithm;
+use map::fov_map::FovMap;
+use map::generator::Generator;
+use map::map::{make_torch, Map, AMBIENT_LIGHT};
+use map::movement_helper::{move_astar, move_by};
+use map::pathfinding::find_path;
+use map::tile::Tile;
+use rng::GameRng;
 use ui::messages::Messages;
 
-pub struct Game {
-    map: Map,
-    messages: Messages,
+pub const PLAYER_IDX: usize = 0;
+pub const INVENTORY_SIZE: usize = 26;
+//Monsters run once their hp drops to a quarter of the maximum
+const FLEE_HP_DIVISOR: i32 = 4;
+
+// experience and level-ups
+const LEVEL_UP_BASE: i32 = 200;
+const LEVEL_UP_FACTOR: i32 = 150;
+const LEVEL_UP_HP: i32 = 20;
+
+//How far monsters can spot something standing in full light. Darker tiles shrink this
+const PERCEPTION_RANGE: f32 = 12.0;
+//Past the perception range, monsters still get suspicious out to this multiple of it
+const GLIMPSE_FACTOR: f32 = 2.0;
+//Turns a suspicious monster spends investigating, and a monster that lost its prey spends searching
+const SUSPICION_TURNS: i32 = 10;
+const SEARCH_TURNS: i32 = 8;
+
+//How far the player can see by default, lit or not
+pub const DEFAULT_SIGHT_RADIUS: i32 = 20;
+
+//Turns of fuel left when the player is warned their light is running out
+const LOW_FUEL_WARNING: i32 = 20;
+
+//Everything needed to simulate a run. Holds no tcod console or input state,
+//so a game can be driven and inspected without opening a window.
+#[derive(Serialize, Deserialize)]
+pub struct GameState {
+    pub seed: u64,
+    pub rng: GameRng, //For everything random once the level is generated
+    pub dungeon_level: u32,
+    pub map: Map,
+    pub fov: FovMap, //What the player can see
+    pub messages: Messages,
+    pub objects: Vec<Object>,
+    pub inventory: Vec<Object>,
+    pub clock: u32, //Turns taken, which animated lights follow
+    pub sight_radius: i32,
+    pub fov_algorithm: FovAlgorithm, //Used for the player, monsters and lights alike
+    pub generator: Option<Generator>, //Builds every level when set, instead of each level's own
+}
+
+//What the player wants to do this turn, independent of how it was input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerCommand {
+    Move(i32, i32),
+    PickUp,
+    Drop(usize),
+    Use(usize, Option<(i32, i32)>),
+    Explore,
+    TravelTo(i32, i32),
+    Descend,
+    CloseDoors,
+}
+
+//The stat a player raises when they level up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelUpChoice {
+    Hp,
+    Power,
+    Defense,
+}
+
+//Commands the player can leave running over many turns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutoMove {
+    Explore,
+    Travel(i32, i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TurnOutcome {
+    TookTurn,
+    DidntTakeTurn,
+    PlayerDead,
+}
+
+impl GameState {
+    //The same seed always generates the same dungeon. Every level is carved out by generator
+    //if one is given, otherwise each level picks its own
+    pub fn new(width: i32, height: i32, seed: u64, generator: Option<Generator>) -> Self {
+        let dungeon_level = 1;
+        let mut map = Map::new(width, height);
+        let objects = map.make_rand_map(
+            new_player(),
+            dungeon_level,
+            generator.unwrap_or_else(|| Generator::for_level(dungeon_level)),
+            &mut level_rng(seed, dungeon_level),
+        );
+
+        // the player starts out carrying a lit torch
+        let mut torch = make_torch(0, 0);
+        if let Some(equipment) = torch.equipment.as_mut() {
+            equipment.equipped = true;
+        }
+
+        let mut game = GameState {
+            seed,
+            rng: GameRng::new(seed),
+            dungeon_level,
+            map,
+            fov: FovMap::new(width, height),
+            messages: Messages::new(),
+            objects,
+            inventory: vec![torch],
+            clock: 0,
+            sight_radius: DEFAULT_SIGHT_RADIUS,
+            fov_algorithm: FovAlgorithm::Symmetric,
+            generator,
+        };
+        game.update_fov();
+        game
+    }
+
+    pub fn player(&self) -> &Object {
+        &self.objects[PLAYER_IDX]
+    }
+
+    //Only the player carries an inventory, so only the player can have anything equipped
+    pub fn equipped(&self, id: usize) -> Vec<Equipment> {
+        if id != PLAYER_IDX {
+            return vec![];
+        }
+        self.inventory
+            .iter()
+            .filter_map(|item| item.equipment)
+            .filter(|equipment| equipment.equipped)
+            .collect()
+    }
+
+    /// power including equipment bonuses
+    pub fn power(&self, id: usize) -> i32 {
+        let base_power = self.objects[id].fighter.map_or(0, |f| f.power);
+        let bonus: i32 = self.equipped(id).iter().map(|e| e.power_bonus).sum();
+        base_power + bonus
+    }
+
+    /// defense including equipment bonuses
+    pub fn defense(&self, id: usize) -> i32 {
+        let base_defense = self.objects[id].fighter.map_or(0, |f| f.defense);
+        let bonus: i32 = self.equipped(id).iter().map(|e| e.defense_bonus).sum();
+        base_defense + bonus
+    }
+
+    /// max hp including equipment bonuses
+    pub fn max_hp(&self, id: usize) -> i32 {
+        let base_max_hp = self.objects[id].fighter.map_or(0, |f| f.max_hp);
+        let bonus: i32 = self.equipped(id).iter().map(|e| e.max_hp_bonus).sum();
+        base_max_hp + bonus
+    }
+
+    ///
+    /// Resolves a single player command, then lets every monster take its turn.
+    ///
+    pub fn step(&mut self, command: PlayerCommand) -> TurnOutcome {
+        if !self.objects[PLAYER_IDX].alive {
+            return TurnOutcome::PlayerDead;
+        }
+
+        let took_turn = match command {
+            PlayerCommand::Move(dx, dy) => {
+                self.player_move_or_attack(dx, dy);
+                true
+            }
+            PlayerCommand::PickUp => self.pick_item_up(),
+            PlayerCommand::Drop(inventory_id) => self.drop_item(inventory_id),
+            PlayerCommand::Use(inventory_id, target) => {
+                inventory_id < self.inventory.len()
+                    && item::use_item(inventory_id, target, self) != UseResult::Cancelled
+            }
+            PlayerCommand::Explore => self.explore(),
+            PlayerCommand::TravelTo(x, y) => self.travel_to(x, y),
+            PlayerCommand::Descend => return self.descend(),
+            PlayerCommand::CloseDoors => self.close_doors(),
+        };
+        if !took_turn {
+            return TurnOutcome::DidntTakeTurn;
+        }
+        // animated lights move on with the turn, so they light the same way however it's shown
+        self.clock = self.clock.wrapping_add(1);
+        self.burn_fuel();
+        self.update_fov();
+
+        for id in 0..self.objects.len() {
+            // only if object is not player
+            if self.objects[id].ai.is_some() {
+                self.ai_turn(id);
+            }
+        }
+        self.update_fov();
+
+        if self.objects[PLAYER_IDX].alive {
+            TurnOutcome::TookTurn
+        } else {
+            TurnOutcome::PlayerDead
+        }
+    }
+
+    //First get the player's line of sight, and use that to mark whether a tile is visible or not.
+    //Next for each object with a light, light every tile it can reach within its raduis
+    pub fn update_fov(&mut self) {
+        self.objects[PLAYER_IDX].light = self.player_light();
+        let (x, y) = self.objects[PLAYER_IDX].get_pos();
+        self.map
+            .compute_fov(x, y, self.sight_radius, self.fov_algorithm, &mut self.fov);
+        self.update_lighting();
+        self.map.mark_explored(&self.fov);
+    }
+
+    //Switches everything over to the next FOV algorithm, and says which one is in use
+    pub fn cycle_fov_algorithm(&mut self) {
+        self.fov_algorithm = self.fov_algorithm.next();
+        self.messages.add(
+            format!("Field of view: {}", self.fov_algorithm),
+            colors::LIGHT_GREY,
+        );
+        self.update_fov();
+    }
+
+    //Light on a tile as the player sees it. Tiles out of view are only remembered, in the dark
+    pub fn light_seen_at(&self, x: i32, y: i32) -> Illumination {
+        if self.fov.is_visible(x, y) {
+            self.map.get_tile(x, y).light
+        } else {
+            Illumination::grey(AMBIENT_LIGHT)
+        }
+    }
+
+    fn update_lighting(&mut self) {
+        self.map.clear_lighting();
+        for (id, obj) in self.objects.iter().enumerate() {
+            if let Some(light) = obj.light.and_then(|light| light.at(self.clock, id as u32)) {
+                let (x, y) = obj.get_pos();
+                self.map
+                    .light_tiles(x, y, obj.facing, &light, self.fov_algorithm)
+            }
+        }
+    }
+
+    //The player is lit by the brightest of whatever they have equipped that is still burning
+    fn player_light(&self) -> Option<LightSource> {
+        self.equipped(PLAYER_IDX)
+            .iter()
+            .filter_map(|equipment| equipment.lit())
+            .max_by(|a, b| {
+                a.max_dist
+                    .partial_cmp(&b.max_dist)
+                    .unwrap_or(Ordering::Equal)
+            })
+    }
+
+    //Every equipped light with fuel burns a turn's worth of it
+    fn burn_fuel(&mut self) {
+        for item in self.inventory.iter_mut() {
+            let equipment = match item.equipment.as_mut() {
+                Some(equipment) if equipment.equipped && equipment.light.is_some() => equipment,
+                _ => continue,
+            };
+            if let Some(fuel) = equipment.fuel.as_mut() {
+                if *fuel <= 0 {
+                    continue;
+                }
+                *fuel -= 1;
+                if *fuel == LOW_FUEL_WARNING {
+                    self.messages.add(
+                        format!("Your {} starts to sputter.", item.name),
+                        colors::ORANGE,
+                    );
+                } else if *fuel == 0 {
+                    self.messages
+                        .add(format!("Your {} burns out!", item.name), colors::RED);
+                }
+            }
+        }
+    }
+
+    fn player_move_or_attack(&mut self, dx: i32, dy: i32) {
+        // the coordinates the player is moving to/attacking
+        let (x, y) = self.objects[PLAYER_IDX].get_pos();
+
+        let new_x = x + dx;
+        let new_y = y + dy;
+
+        // try to find an attackable object there (moving by (0, 0) just waits)
+        let target_id = self.objects.iter().enumerate().position(|(id, object)| {
+            id != PLAYER_IDX && object.fighter.is_some() && object.get_pos() == (new_x, new_y)
+        });
+
+        // attack if target found, move otherwise
+        match target_id {
+            Some(target_id) => self.attack(PLAYER_IDX, target_id),
+            None => {
+                move_by(PLAYER_IDX, dx, dy, &mut self.map, &mut self.objects);
+            }
+        }
+    }
+
+    //Closes the open doors around the player, unless something is standing in the way
+    fn close_doors(&mut self) -> bool {
+        let (x, y) = self.objects[PLAYER_IDX].get_pos();
+        let mut closed_any = false;
+        for door_x in x - 1..=x + 1 {
+            for door_y in y - 1..=y + 1 {
+                let open_door = self.map.valid_point(door_x, door_y)
+                    && self.map.get_tile(door_x, door_y).is_open_door();
+                let in_the_way = self
+                    .objects
+                    .iter()
+                    .any(|object| object.get_pos() == (door_x, door_y));
+                if open_door && !in_the_way {
+                    self.map.map[door_x as usize][door_y as usize].close_door();
+                    closed_any = true;
+                }
+            }
+        }
+
+        if closed_any {
+            self.messages.add("You close the door.", colors::WHITE);
+        } else {
+            self.messages
+                .add("There is no open door to close.", colors::WHITE);
+        }
+        closed_any
+    }
+
+    //Add the first item under the player to the inventory, and remove it from the map
+    fn pick_item_up(&mut self) -> bool {
+        let player_pos = self.objects[PLAYER_IDX].get_pos();
+        let item_id = self
+            .objects
+            .iter()
+            .position(|object| object.get_pos() == player_pos && object.item.is_some());
+
+        match item_id {
+            Some(item_id) => {
+                if self.inventory.len() >= INVENTORY_SIZE {
+                    self.messages.add(
+                        format!(
+                            "Your inventory is full, cannot pick up {}.",
+                            self.objects[item_id].name
+                        ),
+                        colors::RED,
+                    );
+                    return false;
+                }
+                let item = self.objects.swap_remove(item_id);
+                self.messages
+                    .add(format!("You picked up a {}!", item.name), colors::GREEN);
+                self.inventory.push(item);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// experience needed for the player's next level
+    pub fn level_up_xp(&self) -> i32 {
+        LEVEL_UP_BASE + self.objects[PLAYER_IDX].level * LEVEL_UP_FACTOR
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.objects[PLAYER_IDX]
+            .fighter
+            .map_or(false, |f| f.xp >= self.level_up_xp())
+    }
+
+    ///
+    /// Spends the experience for one level, raising the chosen stat.
+    /// Does nothing if the player doesn't have enough experience yet.
+    ///
+    pub fn level_up(&mut self, choice: LevelUpChoice) {
+        if !self.can_level_up() {
+            return;
+        }
+        let level_up_xp = self.level_up_xp();
+        let player = &mut self.objects[PLAYER_IDX];
+        player.level += 1;
+        self.messages.add(
+            format!(
+                "Your battle skills grow stronger! You reached level {}!",
+                player.level
+            ),
+            colors::YELLOW,
+        );
+
+        if let Some(fighter) = player.fighter.as_mut() {
+            fighter.xp -= level_up_xp;
+            match choice {
+                LevelUpChoice::Hp => {
+                    fighter.max_hp += LEVEL_UP_HP;
+                    fighter.hp += LEVEL_UP_HP;
+                }
+                LevelUpChoice::Power => fighter.power += 1,
+                LevelUpChoice::Defense => fighter.defense += 1,
+            }
+        }
+    }
+
+    ///
+    /// Takes one turn of an auto-move. Returns false once it's finished, or
+    /// should be interrupted because a monster is in view or something was logged.
+    /// Won't take a step at all with a monster already in view.
+    ///
+    pub fn auto_step(&mut self, auto_move: AutoMove) -> bool {
+        if !self.visible_monsters().is_empty() {
+            self.messages
+                .add("Not with a monster in view!", colors::WHITE);
+            return false;
+        }
+        let messages_before = self.messages.len();
+
+        let command = match auto_move {
+            AutoMove::Explore => PlayerCommand::Explore,
+            AutoMove::Travel(x, y) => PlayerCommand::TravelTo(x, y),
+        };
+        if self.step(command) != TurnOutcome::TookTurn {
+            return false;
+        }
+
+        self.visible_monsters().is_empty() && self.messages.len() == messages_before
+    }
+
+    pub fn visible_monsters(&self) -> Vec<usize> {
+        self.objects
+            .iter()
+            .enumerate()
+            .filter(|&(_, object)| object.ai.is_some() && self.fov.is_in_fov(object))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    //Walks the player one step towards an explored tile, only going through explored tiles
+    fn travel_to(&mut self, target_x: i32, target_y: i32) -> bool {
+        let (x, y) = self.objects[PLAYER_IDX].get_pos();
+        if (x, y) == (target_x, target_y) {
+            return false;
+        }
+        if !self.map.valid_point(target_x, target_y)
+            || walk_cost(&self.map.get_tile(target_x, target_y), true).is_none()
+        {
+            self.messages.add("You can't go there.", colors::WHITE);
+            return false;
+        }
+
+        let known_cost = |tile: &Tile| {
+            if tile.explored {
+                walk_cost(tile, true)
+            } else {
+                None
+            }
+        };
+        let travel_map = self.map.dijkstra_map(&[(target_x, target_y)], known_cost);
+        match travel_map.downhill(x, y) {
+            Some((next_x, next_y)) => move_by(
+                PLAYER_IDX,
+                next_x - x,
+                next_y - y,
+                &mut self.map,
+                &mut self.objects,
+            ),
+            None => {
+                self.messages
+                    .add("You don't know a way there.", colors::WHITE);
+                false
+            }
+        }
+    }
+
+    //Walks the player one step towards the closest unexplored tile it can reach
+    fn explore(&mut self) -> bool {
+        let mut unexplored = vec![];
+        for x in 0..self.map.width {
+            for y in 0..self.map.height {
+                let tile = self.map.get_tile(x, y);
+                if !tile.explored && !tile.blocked {
+                    unexplored.push((x, y));
+                }
+            }
+        }
+
+        let explore_map = self
+            .map
+            .dijkstra_map(&unexplored, |tile| walk_cost(tile, true));
+        let (x, y) = self.objects[PLAYER_IDX].get_pos();
+        match explore_map.downhill(x, y) {
+            Some((next_x, next_y)) => move_by(
+                PLAYER_IDX,
+                next_x - x,
+                next_y - y,
+                &mut self.map,
+                &mut self.objects,
+            ),
+            None => {
+                self.messages
+                    .add("There is nothing left to explore.", colors::WHITE);
+                false
+            }
+        }
+    }
+
+    //Takes the stairs, if the player is standing on them
+    fn descend(&mut self) -> TurnOutcome {
+        let player_pos = self.objects[PLAYER_IDX].get_pos();
+        let on_stairs = self
+            .objects
+            .iter()
+            .any(|object| object.stairs && object.get_pos() == player_pos);
+        if !on_stairs {
+            self.messages
+                .add("There are no stairs here.", colors::WHITE);
+            return TurnOutcome::DidntTakeTurn;
+        }
+        self.next_level();
+        TurnOutcome::TookTurn
+    }
+
+    /// Advance to the next level, carrying the player over
+    fn next_level(&mut self) {
+        self.messages.add(
+            "You take a moment to rest, and recover your strength.",
+            colors::VIOLET,
+        );
+        let max_hp = self.max_hp(PLAYER_IDX);
+        self.objects[PLAYER_IDX].heal(max_hp / 2, max_hp);
+
+        self.messages.add(
+            "After a rare moment of peace, you descend deeper into \
+             the heart of the dungeon...",
+            colors::RED,
+        );
+        self.dungeon_level += 1;
+
+        let player = self.objects.swap_remove(PLAYER_IDX);
+        self.map = Map::new(self.map.width, self.map.height);
+        self.objects = self.map.make_rand_map(
+            player,
+            self.dungeon_level,
+            self.generator
+                .unwrap_or_else(|| Generator::for_level(self.dungeon_level)),
+            &mut level_rng(self.seed, self.dungeon_level),
+        );
+        self.update_fov();
+    }
+
+    fn drop_item(&mut self, inventory_id: usize) -> bool {
+        if inventory_id >= self.inventory.len() {
+            return false;
+        }
+        if self.inventory[inventory_id]
+            .equipment
+            .map_or(false, |e| e.equipped)
+        {
+            item::dequip(inventory_id, self);
+        }
+        let mut item = self.inventory.remove(inventory_id);
+        let (x, y) = self.objects[PLAYER_IDX].get_pos();
+        item.set_pos(x, y);
+        self.messages
+            .add(format!("You dropped a {}.", item.name), colors::YELLOW);
+        self.objects.push(item);
+        true
+    }
+
+    fn ai_turn(&mut self, monster_id: usize) {
+        use entities::object::Ai::*;
+        if let Some(ai) = self.objects[monster_id].ai.take() {
+            let new_ai = match ai {
+                Basic { awareness } => self.ai_basic(monster_id, awareness),
+                Confused {
+                    previous_ai,
+                    num_turns,
+                } => self.ai_confused(monster_id, previous_ai, num_turns),
+            };
+            self.objects[monster_id].ai = Some(new_ai);
+        }
+    }
+
+    fn ai_basic(&mut self, monster_id: usize, awareness: Awareness) -> Ai {
+        let (x, y) = self.objects[monster_id].get_pos();
+        // far enough to catch a glimpse of something in full light
+        let radius = (GLIMPSE_FACTOR * PERCEPTION_RANGE).ceil() as i32;
+        let in_view = self.map.field_of_view(x, y, radius, self.fov_algorithm);
+        let target_id = self.nearest_target(monster_id, &in_view);
+        let spotted = target_id.filter(|&target_id| self.perceives(monster_id, target_id));
+
+        // first work out what the monster knows now
+        let awareness = match (spotted, target_id) {
+            (Some(target_id), _) => {
+                let was_hunting = match awareness {
+                    Awareness::Hunting { .. } => true,
+                    _ => false,
+                };
+                if !was_hunting {
+                    self.notice(monster_id);
+                }
+                let (target_x, target_y) = self.objects[target_id].get_pos();
+                Awareness::Hunting {
+                    x: target_x,
+                    y: target_y,
+                }
+            }
+            (None, Some(target_id)) if self.glimpses(monster_id, target_id) => {
+                let (target_x, target_y) = self.objects[target_id].get_pos();
+                match awareness {
+                    Awareness::Hunting { .. } => Awareness::Hunting {
+                        x: target_x,
+                        y: target_y,
+                    },
+                    _ => Awareness::Suspicious {
+                        x: target_x,
+                        y: target_y,
+                        turns: SUSPICION_TURNS,
+                    },
+                }
+            }
+            _ => awareness,
+        };
+
+        // then act on it
+        let awareness = match awareness {
+            Awareness::Unaware => Awareness::Unaware,
+            Awareness::Hunting {
+                x: last_x,
+                y: last_y,
+            } => match spotted {
+                Some(target_id) => {
+                    self.chase(monster_id, target_id);
+                    awareness
+                }
+                None if (x, y) == (last_x, last_y) => Awareness::LostTrack {
+                    turns: SEARCH_TURNS,
+                },
+                None => {
+                    let opens_doors = self.objects[monster_id].opens_doors;
+                    let path = find_path(
+                        &self.map,
+                        &self.objects,
+                        (x, y),
+                        (last_x, last_y),
+                        opens_doors,
+                    );
+                    match path.and_then(|path| path.first().cloned()) {
+                        Some((next_x, next_y)) => {
+                            let moved = move_by(
+                                monster_id,
+                                next_x - x,
+                                next_y - y,
+                                &mut self.map,
+                                &mut self.objects,
+                            );
+                            // something else is standing where the target was last seen
+                            if !moved && (next_x, next_y) == (last_x, last_y) {
+                                Awareness::LostTrack {
+                                    turns: SEARCH_TURNS,
+                                }
+                            } else {
+                                awareness
+                            }
+                        }
+                        // there's no way to where the target was last seen
+                        None => Awareness::LostTrack {
+                            turns: SEARCH_TURNS,
+                        },
+                    }
+                }
+            },
+            Awareness::Suspicious {
+                x: suspect_x,
+                y: suspect_y,
+                turns,
+            } => {
+                if turns <= 0 || (x, y) == (suspect_x, suspect_y) {
+                    Awareness::Unaware
+                } else {
+                    move_astar(
+                        monster_id,
+                        suspect_x,
+                        suspect_y,
+                        &mut self.map,
+                        &mut self.objects,
+                    );
+                    Awareness::Suspicious {
+                        x: suspect_x,
+                        y: suspect_y,
+                        turns: turns - 1,
+                    }
+                }
+            }
+            Awareness::LostTrack { turns } => {
+                if turns <= 0 {
+                    Awareness::Unaware
+                } else {
+                    // search around where the target was last seen
+                    let (dx, dy) = (self.rng.gen_range(-1, 2), self.rng.gen_range(-1, 2));
+                    move_by(monster_id, dx, dy, &mut self.map, &mut self.objects);
+                    Awareness::LostTrack { turns: turns - 1 }
+                }
+            }
+        };
+        Ai::Basic { awareness }
+    }
+
+    //Runs from a target it's losing to, otherwise closes in and attacks
+    fn chase(&mut self, monster_id: usize, target_id: usize) {
+        if self.is_badly_hurt(monster_id) && self.flee_from(monster_id, target_id) {
+            return;
+        }
+
+        if self.objects[monster_id].distance_to(&self.objects[target_id]) >= 2.0 {
+            let (target_x, target_y) = self.objects[target_id].get_pos();
+            move_astar(
+                monster_id,
+                target_x,
+                target_y,
+                &mut self.map,
+                &mut self.objects,
+            );
+        } else if self.objects[target_id].fighter.map_or(false, |f| f.hp > 0) {
+            self.attack(monster_id, target_id);
+        }
+    }
+
+    //Lets the player know they've been spotted, if they can see it happen
+    fn notice(&mut self, monster_id: usize) {
+        if self.fov.is_in_fov(&self.objects[monster_id]) {
+            self.messages.add(
+                format!("The {} notices you!", self.objects[monster_id].name),
+                colors::ORANGE,
+            );
+        }
+    }
+
+    //Melee attack, using the effective stats of both sides
+    fn attack(&mut self, attacker_id: usize, target_id: usize) {
+        let damage = self.power(attacker_id) - self.defense(target_id);
+        let (attacker, target) = mut_two(attacker_id, target_id, &mut self.objects);
+        let (attacker_x, attacker_y) = attacker.get_pos();
+        let (target_x, target_y) = target.get_pos();
+        attacker.face(target_x - attacker_x, target_y - attacker_y);
+        attacker.attack(target, damage, &mut self.messages);
+    }
+
+    ///
+    /// The closest thing a monster can actually walk to, out of everything it wants to kill
+    /// and has in view.
+    ///
+    fn nearest_target(&self, monster_id: usize, in_view: &FovMap) -> Option<usize> {
+        let candidates: Vec<_> = self
+            .objects
+            .iter()
+            .enumerate()
+            .filter(|&(_, object)| {
+                object.alive
+                    && object
+                        .fighter
+                        .map_or(false, |f| f.on_death == DeathCallback::Player)
+                    && in_view.is_in_fov(object)
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let (x, y) = self.objects[monster_id].get_pos();
+        let opens_doors = self.objects[monster_id].opens_doors;
+        let distances = self
+            .map
+            .dijkstra_map(&[(x, y)], |tile| walk_cost(tile, opens_doors));
+        candidates
+            .into_iter()
+            .filter_map(|(id, object)| {
+                let (target_x, target_y) = object.get_pos();
+                distances
+                    .value(target_x, target_y)
+                    .map(|distance| (distance, id))
+            })
+            .min()
+            .map(|(_, id)| id)
+    }
+
+    //Monsters spot their target from further away the better lit it is, and always up close
+    fn perceives(&self, monster_id: usize, target_id: usize) -> bool {
+        let distance = self.objects[monster_id].distance_to(&self.objects[target_id]);
+        distance < 2.0 || distance <= self.detection_range(target_id)
+    }
+
+    //Too far to make out in this light, but close enough that something seems off
+    fn glimpses(&self, monster_id: usize, target_id: usize) -> bool {
+        let distance = self.objects[monster_id].distance_to(&self.objects[target_id]);
+        distance <= GLIMPSE_FACTOR * self.detection_range(target_id)
+    }
+
+    /// how close a monster has to be to notice the object, given the light it stands in
+    pub fn detection_range(&self, id: usize) -> f32 {
+        let (x, y) = self.objects[id].get_pos();
+        PERCEPTION_RANGE * self.map.get_tile(x, y).light.brightness()
+    }
+
+    fn is_badly_hurt(&self, monster_id: usize) -> bool {
+        let max_hp = self.max_hp(monster_id);
+        self.objects[monster_id]
+            .fighter
+            .map_or(false, |f| f.hp * FLEE_HP_DIVISOR <= max_hp)
+    }
+
+    //Steps away from the target, returns false if the monster is cornered
+    fn flee_from(&mut self, monster_id: usize, target_id: usize) -> bool {
+        let target_pos = self.objects[target_id].get_pos();
+        let opens_doors = self.objects[monster_id].opens_doors;
+        //The best escape route must never lead through whatever is being fled from
+        let flee_cost = |tile: &Tile| {
+            if (tile.x, tile.y) == target_pos {
+                None
+            } else {
+                walk_cost(tile, opens_doors)
+            }
+        };
+        let flee_map = self
+            .map
+            .dijkstra_map(&[target_pos], |tile| walk_cost(tile, opens_doors))
+            .inverted(&self.map, flee_cost);
+
+        let (x, y) = self.objects[monster_id].get_pos();
+        if let Some((next_x, next_y)) = flee_map.downhill(x, y) {
+            move_by(
+                monster_id,
+                next_x - x,
+                next_y - y,
+                &mut self.map,
+                &mut self.objects,
+            );
+        }
+        self.objects[monster_id].get_pos() != (x, y)
+    }
+
+    fn ai_confused(&mut self, monster_id: usize, previous_ai: Box<Ai>, num_turns: i32) -> Ai {
+        if num_turns >= 0 {
+            // still confused ...
+            // move in a random direction, and decrease the number of turns confused
+            let (dx, dy) = (self.rng.gen_range(-1, 2), self.rng.gen_range(-1, 2));
+            move_by(monster_id, dx, dy, &mut self.map, &mut self.objects);
+            Ai::Confused {
+                previous_ai: previous_ai,
+                num_turns: num_turns - 1,
+            }
+        } else {
+            // restore the previous AI (this one will be deleted)
+            self.messages.add(
+                format!(
+                    "The {} is no longer confused!",
+                    self.objects[monster_id].name
+                ),
+                colors::RED,
+            );
+            *previous_ai
+        }
+    }
+}
+
+//Each level gets its own generator, so a level is the same no matter how the player got there
+fn level_rng(seed: u64, dungeon_level: u32) -> Isaac64Rng {
+    Isaac64Rng::from_seed(&[seed, dungeon_level as u64])
+}
+
+fn new_player() -> Object {
+    let mut player = Object::new(0, 0, '@', "player", colors::WHITE, true);
+    player.alive = true;
+    player.opens_doors = true;
+    player.fighter = Some(Fighter {
+        max_hp: 30,
+        hp: 30,
+        defense: 2,
+        power: 5,
+        xp: 0,
+        on_death: DeathCallback::Player,
+    });
+    player
+}
+
+/// Mutably borrow two *separate* elements from the given slice.
+/// Panics when the indexes are equal or out of bounds.
+fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
+    assert!(first_index != second_index);
+    let split_at_index = cmp::max(first_index, second_index);
+    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
+    if first_index < second_index {
+        (&mut first_slice[first_index], &mut second_slice[0])
+    } else {
+        (&mut second_slice[0], &mut first_slice[second_index])
+    }
+}
+
+#[cfg(test)]
+impl GameState {
+    //A game on a fixture map (see Map::from_rows), with the player carrying their usual torch
+    //and nothing else around but an orc at each of the given spots
+    pub fn on_fixture(rows: &[&str], player: (i32, i32), monsters: &[(i32, i32)]) -> Self {
+        let mut game = GameState::new(80, 43, 7, None);
+        game.map = Map::from_rows(rows);
+        game.fov = FovMap::new(game.map.width, game.map.height);
+        game.objects.truncate(1);
+        game.objects[PLAYER_IDX].set_pos(player.0, player.1);
+        for &(x, y) in monsters.iter() {
+            let mut monster = Object::new(x, y, 'o', "orc", colors::WHITE, true);
+            monster.alive = true;
+            monster.fighter = Some(Fighter {
+                max_hp: 10,
+                hp: 10,
+                defense: 0,
+                power: 3,
+                xp: 35,
+                on_death: DeathCallback::Monster,
+            });
+            monster.ai = Some(Ai::basic());
+            game.objects.push(monster);
+        }
+        game.update_fov();
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::item::Item;
+    use serde_json;
+    use ui::ascii::render_ascii;
+
+    fn explored_tiles(game: &GameState) -> usize {
+        let mut explored = 0;
+        for x in 0..game.map.width {
+            for y in 0..game.map.height {
+                if game.map.get_tile(x, y).explored {
+                    explored += 1;
+                }
+            }
+        }
+        explored
+    }
+
+    //Everything generated for a level, tiles and objects alike
+    fn dungeon(game: &GameState) -> (String, String) {
+        (
+            serde_json::to_string(&game.map).unwrap(),
+            serde_json::to_string(&game.objects).unwrap(),
+        )
+    }
+
+    #[test]
+    fn the_same_seed_generates_the_same_dungeon() {
+        let mut first = GameState::new(80, 43, 42, None);
+        let mut second = GameState::new(80, 43, 42, None);
+        assert_eq!(dungeon(&first), dungeon(&second));
+
+        // deeper levels too, however long the player took to get there. Only the lighting
+        // differs, as animated lights follow the turns taken
+        first.step(PlayerCommand::Move(0, 0));
+        first.next_level();
+        second.next_level();
+        assert_eq!(
+            render_ascii(&first.map, &first.objects, None),
+            render_ascii(&second.map, &second.objects, None)
+        );
+    }
+
+    #[test]
+    fn a_different_seed_generates_a_different_dungeon() {
+        let first = GameState::new(80, 43, 42, None);
+        let second = GameState::new(80, 43, 43, None);
+        let (first_map, first_objects) = dungeon(&first);
+        let (second_map, second_objects) = dungeon(&second);
+        assert_ne!(first_map, second_map);
+        assert_ne!(first_objects, second_objects);
+    }
+
+    #[test]
+    fn searching_monsters_wander_the_same_way_for_the_same_seed() {
+        let games: Vec<GameState> = (0..2)
+            .map(|_| {
+                let mut game = GameState::new(80, 43, 42, None);
+                for object in game.objects.iter_mut().filter(|o| o.ai.is_some()) {
+                    object.ai = Some(Ai::Basic {
+                        awareness: Awareness::LostTrack {
+                            turns: SEARCH_TURNS,
+                        },
+                    });
+                }
+                for _ in 0..SEARCH_TURNS {
+                    game.step(PlayerCommand::Move(0, 0));
+                }
+                game
+            })
+            .collect();
+        assert_eq!(dungeon(&games[0]).1, dungeon(&games[1]).1);
+    }
+
+    #[test]
+    fn only_stairs_lead_down() {
+        let mut game = GameState::new(80, 43, 7, None);
+        let (x, y) = game.player().get_pos();
+        // a scroll that happens to be called stairs is still just a scroll
+        let mut scroll = Object::new(x, y, '>', "stairs", colors::WHITE, false);
+        scroll.item = Some(Item::Confuse);
+        game.objects.push(scroll);
+        assert_eq!(
+            game.step(PlayerCommand::Descend),
+            TurnOutcome::DidntTakeTurn
+        );
+
+        let stairs = game.objects.iter().position(|o| o.stairs).unwrap();
+        game.objects[stairs].name = "way down".into();
+        let (stairs_x, stairs_y) = game.objects[stairs].get_pos();
+        game.objects[PLAYER_IDX].set_pos(stairs_x, stairs_y);
+        assert_eq!(game.step(PlayerCommand::Descend), TurnOutcome::TookTurn);
+        assert_eq!(game.dungeon_level, 2);
+    }
+
+    #[test]
+    fn travelling_into_a_wall_takes_no_turn() {
+        let mut game = GameState::new(80, 43, 7, None);
+        let (x, y) = game.player().get_pos();
+        let wall_x = (x..game.map.width)
+            .find(|&wall_x| !game.map.get_tile(wall_x, y).passable())
+            .unwrap();
+        game.map.map[wall_x as usize][y as usize].explored = true;
+
+        assert!(!game.auto_step(AutoMove::Travel(wall_x, y)));
+        assert_eq!(game.player().get_pos(), (x, y));
+        assert_eq!(
+            game.messages.iter().last().unwrap().0,
+            "You can't go there."
+        );
+    }
+
+    #[test]
+    fn auto_moves_stop_for_monsters_already_in_view() {
+        let mut game = GameState::new(80, 43, 7, None);
+        assert!(game.visible_monsters().is_empty());
+        let (x, y) = game.player().get_pos();
+        let monster = game.objects.iter().position(|o| o.ai.is_some()).unwrap();
+        game.objects[monster].set_pos(x + 1, y);
+        game.update_fov();
+
+        assert!(!game.auto_step(AutoMove::Explore));
+        assert_eq!(game.player().get_pos(), (x, y));
+    }
+
+    #[test]
+    fn plays_turns_without_a_window() {
+        let mut game = GameState::new(80, 43, 7, None);
+        let start = game.player().get_pos();
+        let explored_at_start = explored_tiles(&game);
+
+        // waiting still lets the monsters act
+        for _ in 0..5 {
+            assert_eq!(game.step(PlayerCommand::Move(0, 0)), TurnOutcome::TookTurn);
+        }
+        assert_eq!(game.player().get_pos(), start);
+        // there's nothing to pick up where the player starts, so no turn passes
+        assert_eq!(game.step(PlayerCommand::PickUp), TurnOutcome::DidntTakeTurn);
+
+        // explore until something gets in the way
+        let mut turns = 0;
+        while turns < 20 && game.step(PlayerCommand::Explore) == TurnOutcome::TookTurn {
+            turns += 1;
+        }
+        assert!(turns > 0);
+        assert_ne!(game.player().get_pos(), start);
+        assert!(explored_tiles(&game) > explored_at_start);
+    }
+
+    //The player at the west end of a long, empty corridor and a monster further along it.
+    //Below the corridor is a pocket the player can be taken to, out of sight and reach
+    fn corridor_with_monster(distance: i32) -> GameState {
+        let wall = "#".repeat(40);
+        let corridor = format!("#{}#", ".".repeat(38));
+        let pocket = format!("#.{}", "#".repeat(38));
+        GameState::on_fixture(
+            &[&wall, &corridor, &wall, &pocket, &wall],
+            (1, 1),
+            &[(1 + distance, 1)],
+        )
+    }
+
+    fn awareness(game: &GameState) -> Awareness {
+        match game.objects[1].ai {
+            Some(Ai::Basic { awareness }) => awareness,
+            ref ai => panic!("not a basic ai: {:?}", ai),
+        }
+    }
+
+    //Waits until the monster's awareness passes the check, or gives up after some turns
+    fn wait_for<F: Fn(Awareness) -> bool>(game: &mut GameState, turns: i32, check: F) -> bool {
+        for _ in 0..turns {
+            game.step(PlayerCommand::Move(0, 0));
+            if check(awareness(game)) {
+                return true;
+            }
+        }
+        false
+    }
+
+    //The monster glimpses the player from past its perception range and hunts them down,
+    //then the player is taken out of its sight
+    fn hunt_then_hide(game: &mut GameState) {
+        let glimpse_range = game.detection_range(PLAYER_IDX) * GLIMPSE_FACTOR;
+        assert!(PERCEPTION_RANGE < 15.0 && 15.0 <= glimpse_range);
+        assert_eq!(awareness(game), Awareness::Unaware);
+
+        game.step(PlayerCommand::Move(0, 0));
+        match awareness(game) {
+            Awareness::Suspicious { x: 1, y: 1, .. } => {}
+            other => panic!("expected a suspicious monster, got {:?}", other),
+        }
+        assert!(wait_for(game, SUSPICION_TURNS, |awareness| {
+            awareness == Awareness::Hunting { x: 1, y: 1 }
+        }));
+
+        game.objects[PLAYER_IDX].set_pos(1, 3);
+        game.update_fov();
+    }
+
+    #[test]
+    fn monsters_grow_suspicious_then_hunt_then_lose_track() {
+        let mut game = corridor_with_monster(15);
+        hunt_then_hide(&mut game);
+        assert!(wait_for(&mut game, 20, |awareness| matches!(
+            awareness,
+            Awareness::LostTrack { .. }
+        )));
+        assert_eq!(game.objects[1].get_pos(), (1, 1));
+    }
+
+    #[test]
+    fn hunting_monsters_lose_track_when_the_last_known_spot_is_taken() {
+        let mut game = corridor_with_monster(15);
+        hunt_then_hide(&mut game);
+        game.objects
+            .push(Object::new(1, 1, '0', "boulder", colors::WHITE, true));
+        assert!(wait_for(&mut game, 20, |awareness| matches!(
+            awareness,
+            Awareness::LostTrack { .. }
+        )));
+        assert_eq!(game.objects[1].get_pos(), (2, 1));
+    }
+
+    #[test]
+    fn a_dead_player_takes_no_more_turns() {
+        let mut game = GameState::new(80, 43, 7, None);
+        game.objects[PLAYER_IDX].alive = false;
+        assert_eq!(
+            game.step(PlayerCommand::Move(1, 0)),
+            TurnOutcome::PlayerDead
+        );
+    }
 }
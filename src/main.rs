@@ -1,20 +1,35 @@
 extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate tcod;
 
-use std::{cmp, env};
+use std::env;
+use std::fmt::Display;
+use std::process;
+use std::str::FromStr;
 
-use entities::light;
+use colors::Color;
+use entities::item::{self, Targeting};
 use entities::object::Object;
 
-use map::map::Map;
-use map::movement_helper::{move_by, move_towards};
+use game::{AutoMove, GameState, LevelUpChoice, PlayerCommand, PLAYER_IDX};
+use map::fov_map::FovMap;
+use map::generator::Generator;
+use tcod::console::*;
 use tcod::console::{Offscreen, Root};
 use tcod::input::{self, Event, Key, Mouse};
-use tcod::{colors, console::*, Color};
-use ui::messages::Messages;
+use ui::ascii::{render_ascii, render_light};
+use ui::draw;
+use ui::menu::{menu, menu_over, msgbox};
 
+mod colors;
 mod entities;
+mod game;
 mod map;
+mod rng;
+mod save;
 mod ui;
 
 //use KeyCode::{Up, Down, Left, Right, Escape};
@@ -25,8 +40,6 @@ const LIMIT_FPS: i32 = 20;
 const MAP_WIDTH: i32 = 80;
 const MAP_HEIGHT: i32 = 43;
 
-const PLAYER_IDX: usize = 0;
-
 // sizes and coordinates relevant for the GUI
 const BAR_WIDTH: i32 = 20;
 const PANEL_HEIGHT: i32 = 7;
@@ -37,10 +50,10 @@ const MSG_X: i32 = BAR_WIDTH + 2;
 const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
 const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
 
-struct Game {
-    map: Map,
-    messages: Messages,
-}
+const INVENTORY_WIDTH: i32 = 50;
+const LEVEL_SCREEN_WIDTH: i32 = 40;
+
+const SAVE_FILE: &str = "savegame";
 
 struct Tcod {
     root: Root,
@@ -50,10 +63,37 @@ struct Tcod {
     mouse: Mouse,
 }
 
+//What was asked for on the command line
+struct Options {
+    seed: Option<u64>,
+    generator: Option<Generator>,
+    sight_radius: Option<i32>,
+}
+
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
+
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    // `--print-level` prints a generated level and its lighting as text instead of opening a window
+    if env::args().any(|arg| arg == "--print-level") {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let game = GameState::new(MAP_WIDTH, MAP_HEIGHT, seed, options.generator);
+        println!("Dungeon seed: {}", seed);
+        print!("{}", render_ascii(&game.map, &game.objects, None));
+        println!("Light, as the player first sees it:");
+        print!("{}", render_light(&game.map, Some(&game.fov)));
+        return;
+    }
+
     print!("inited");
-    let mut root: Root = Root::initializer()
+    let root: Root = Root::initializer()
         .font("arial10x10.png", FontLayout::Tcod)
         .font_type(FontType::Greyscale)
         .size(SCREEN_WIDTH, SCREEN_HEIGHT)
@@ -68,69 +108,196 @@ fn main() {
         mouse: Default::default(),
     };
 
-    let mut game = Game {
-        map: Map::new(MAP_WIDTH, MAP_HEIGHT),
-        messages: Messages::new(),
-    };
-    let mut objects = game.map.make_rand_map();
+    tcod::system::set_fps(LIMIT_FPS);
 
-    let mut prev_pos = (-1, -1);
+    main_menu(&mut tcod, &options);
+}
 
-    tcod::system::set_fps(LIMIT_FPS);
+fn main_menu(tcod: &mut Tcod, options: &Options) {
+    while !tcod.root.window_closed() {
+        tcod.root.set_default_background(colors::BLACK.into());
+        tcod.root.clear();
+        tcod.root
+            .set_default_foreground(colors::LIGHT_YELLOW.into());
+        tcod.root.print_ex(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT / 2 - 4,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "Rust Rogue",
+        );
+
+        let choices = &["Play a new game", "Continue last game", "Quit"];
+        let choice = menu("", choices, 24, &mut tcod.root);
+
+        match choice {
+            Some(0) => {
+                let seed = options.seed.unwrap_or_else(rand::random);
+                let mut game = GameState::new(MAP_WIDTH, MAP_HEIGHT, seed, options.generator);
+                if let Some(sight_radius) = options.sight_radius {
+                    game.sight_radius = sight_radius;
+                    game.update_fov();
+                }
+                game.messages.add("Welcome stranger!", colors::RED);
+                game.messages
+                    .add(format!("Dungeon seed: {}", game.seed), colors::LIGHT_GREY);
+                play_game(tcod, &mut game);
+            }
+            Some(1) => match save::load_game(SAVE_FILE) {
+                Ok(mut game) => play_game(tcod, &mut game),
+                Err(e) => msgbox(&format!("\n{}\n", e), 24, &mut tcod.root),
+            },
+            Some(2) => break,
+            _ => {}
+        }
+    }
+}
 
-    game.messages.add("Welcome stranger!", colors::RED);
+fn play_game(tcod: &mut Tcod, game: &mut GameState) {
+    let mut auto_move = None;
 
     while !tcod.root.window_closed() {
         tcod.con.clear();
+        // clicks and key presses only count for the frame they happened in
+        tcod.key = Default::default();
+        tcod.mouse.lbutton_pressed = false;
+        tcod.mouse.rbutton_pressed = false;
         match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
             Some((_, Event::Mouse(m))) => tcod.mouse = m,
             Some((_, Event::Key(k))) => tcod.key = k,
-            _ => tcod.key = Default::default(),
+            _ => {}
         };
 
-        let fov_recompute = prev_pos != objects[0].get_pos();
         //println!("rendering");
-        render_all(&mut tcod, &mut objects, &mut game, fov_recompute);
-
-        // erase all objects at their old locations, before they move
-        for object in &objects {
-            object.clear(&mut tcod.con)
-        }
+        render_all(tcod, game);
 
         // handle keys and exit game if needed
         //println!("handling player movement");
-        let player = &mut objects[0];
-        prev_pos = player.get_pos();
-        let player_action = handle_keys(&mut tcod, &mut game, &mut objects);
-        if player_action == PlayerAction::Exit {
-            break;
+        let action = handle_keys(tcod, game);
+
+        // erase all objects at their old locations, before they move
+        for object in &game.objects {
+            draw::clear(object, &mut tcod.con)
         }
 
-        // let monsters take their turn
-        //println!("monster turns");
-        if objects[PLAYER_IDX].alive && player_action != PlayerAction::DidntTakeTurn {
-            for id in 0..objects.len() {
-                // only if object is not player
-                if (objects[id].ai.is_some()) {
-                    ai_turn(id, &mut game, &mut objects);
+        match action {
+            PlayerAction::Exit => break,
+            PlayerAction::Command(command) => {
+                auto_move = None;
+                game.step(command);
+            }
+            PlayerAction::StartAutoMove(new_auto_move) => auto_move = Some(new_auto_move),
+            PlayerAction::DidntTakeTurn => {
+                // any key press stops whatever the player left running
+                if tcod.key.pressed {
+                    auto_move = None;
                 }
             }
         }
+
+        if let Some(current) = auto_move {
+            if !game.auto_step(current) {
+                auto_move = None;
+            }
+        }
+
+        level_up(tcod, game);
+    }
+
+    //Closing the window or leaving to the menu keeps the run, unless it has ended in death
+    let saved = if game.player().alive {
+        save::save_game(game, SAVE_FILE)
+    } else {
+        save::delete_save(SAVE_FILE)
+    };
+    if let Err(e) = saved {
+        msgbox(&format!("\n{}\n", e), 24, &mut tcod.root);
     }
 }
 
+//Asks the player which stat to raise, for as long as they have experience to spend
+fn level_up(tcod: &mut Tcod, game: &mut GameState) {
+    while game.can_level_up() {
+        let fighter = match game.player().fighter {
+            Some(fighter) => fighter,
+            None => return,
+        };
+        let mut choice = None;
+        while choice.is_none() {
+            // keep asking until a choice is made. If the window is closed instead, the game
+            // loop sees it and ends the run as usual
+            if tcod.root.window_closed() {
+                return;
+            }
+            choice = menu(
+                "Level up! Choose a stat to raise:\n",
+                &[
+                    format!("Constitution (+20 HP, from {})", fighter.max_hp),
+                    format!("Strength (+1 attack, from {})", fighter.power),
+                    format!("Agility (+1 defense, from {})", fighter.defense),
+                ],
+                LEVEL_SCREEN_WIDTH,
+                &mut tcod.root,
+            );
+        }
+        let choice = match choice {
+            Some(0) => LevelUpChoice::Hp,
+            Some(1) => LevelUpChoice::Power,
+            _ => LevelUpChoice::Defense,
+        };
+        game.level_up(choice);
+    }
+}
+
+fn parse_options() -> Result<Options, String> {
+    let sight_radius = parse_flag(env::args(), "--sight-radius")?;
+    // the player always sees at least the tiles around them
+    if let Some(radius) = sight_radius.filter(|&radius: &i32| radius < 1) {
+        return Err(format!(
+            "Bad value for --sight-radius: {} is less than 1",
+            radius
+        ));
+    }
+    Ok(Options {
+        seed: parse_flag(env::args(), "--seed")?,
+        generator: parse_flag(env::args(), "--generator")?,
+        sight_radius,
+    })
+}
+
+//Reads the value of a `--flag <value>` command line option, e.g. the seed of a run to replay.
+//A value that can't be read is an error, rather than quietly falling back to the default
+fn parse_flag<I, T>(mut args: I, flag: &str) -> Result<Option<T>, String>
+where
+    I: Iterator<Item = String>,
+    T: FromStr,
+    T::Err: Display,
+{
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            let value = args.next().unwrap_or_default();
+            return value
+                .parse()
+                .map(Some)
+                .map_err(|e| format!("Bad value for {}: {}", flag, e));
+        }
+    }
+    Ok(None)
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum PlayerAction {
-    TookTurn,
+    Command(PlayerCommand),
+    StartAutoMove(AutoMove),
     DidntTakeTurn,
     Exit,
 }
 
-fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> PlayerAction {
+fn handle_keys(tcod: &mut Tcod, game: &mut GameState) -> PlayerAction {
     use tcod::input::KeyCode::*;
     use PlayerAction::*;
 
-    let player_alive = objects[0].alive;
+    let player_alive = game.player().alive;
     match (tcod.key, tcod.key.text(), player_alive) {
         (
             Key {
@@ -150,112 +317,201 @@ fn handle_keys(tcod: &mut Tcod, game: &mut Game, objects: &mut Vec<Object>) -> P
 
         // movement keys
         //Key { code: Spacebar, .. } => {}
-        (Key { code: Up, .. }, _, true) => {
-            player_move_or_attack(0, -1, game, objects);
-            return TookTurn;
-        }
-        (Key { code: Down, .. }, _, true) => {
-            player_move_or_attack(0, 1, game, objects);
-            return TookTurn;
+        (Key { code: Up, .. }, _, true) => return Command(PlayerCommand::Move(0, -1)),
+        (Key { code: Down, .. }, _, true) => return Command(PlayerCommand::Move(0, 1)),
+        (Key { code: Left, .. }, _, true) => return Command(PlayerCommand::Move(-1, 0)),
+        (Key { code: Right, .. }, _, true) => return Command(PlayerCommand::Move(1, 0)),
+
+        // keep walking towards the nearest unexplored tile
+        (Key { code: Text, .. }, "x", true) => return StartAutoMove(AutoMove::Explore),
+
+        // switch to the next field of view algorithm, to compare them
+        (Key { code: Text, .. }, "f", true) => {
+            game.cycle_fov_algorithm();
+            return DidntTakeTurn;
         }
-        (Key { code: Left, .. }, _, true) => {
-            player_move_or_attack(-1, 0, game, objects);
-            return TookTurn;
+
+        // close the doors next to the player
+        (Key { code: Text, .. }, "c", true) => return Command(PlayerCommand::CloseDoors),
+
+        // go down stairs, if the player is on them
+        (Key { code: Text, .. }, ">", true) => return Command(PlayerCommand::Descend),
+
+        // inventory keys
+        (Key { code: Text, .. }, "g", true) => return Command(PlayerCommand::PickUp),
+        (Key { code: Text, .. }, "i", true) => {
+            // show the inventory: if an item is selected, use it
+            let inventory_index = inventory_menu(
+                &game.inventory,
+                "Press the key next to an item to use it, or any other to cancel.\n",
+                &mut tcod.con,
+                &mut tcod.root,
+            );
+            if let Some(inventory_index) = inventory_index {
+                let targeting = game.inventory[inventory_index]
+                    .item
+                    .map(|item| item.targeting());
+                let target = match targeting {
+                    Some(Targeting::Tile { max_range }) => {
+                        game.messages.add(
+                            "Left-click a target tile, or right-click to cancel.",
+                            colors::LIGHT_CYAN,
+                        );
+                        match target_tile(tcod, game, max_range) {
+                            Some(tile_pos) => Some(tile_pos),
+                            None => return DidntTakeTurn,
+                        }
+                    }
+                    _ => None,
+                };
+                return Command(PlayerCommand::Use(inventory_index, target));
+            }
+            return DidntTakeTurn;
         }
-        (Key { code: Right, .. }, _, true) => {
-            player_move_or_attack(1, 0, game, objects);
-            return TookTurn;
+        (Key { code: Text, .. }, "d", true) => {
+            // show the inventory; if an item is selected, drop it
+            let inventory_index = inventory_menu(
+                &game.inventory,
+                "Press the key next to an item to drop it, or any other to cancel.\n",
+                &mut tcod.con,
+                &mut tcod.root,
+            );
+            if let Some(inventory_index) = inventory_index {
+                return Command(PlayerCommand::Drop(inventory_index));
+            }
+            return DidntTakeTurn;
         }
 
-        _ => return DidntTakeTurn,
+        _ => {}
     }
-}
 
-fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, objects: &mut [Object]) {
-    // the coordinates the player is moving to/attacking
-    let (x, y) = objects[PLAYER_IDX].get_pos();
+    // clicking an explored tile travels there
+    let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
+    if tcod.mouse.lbutton_pressed
+        && player_alive
+        && game.map.valid_point(x, y)
+        && game.map.get_tile(x, y).explored
+    {
+        return StartAutoMove(AutoMove::Travel(x, y));
+    }
+    DidntTakeTurn
+}
 
-    let new_x = x + dx;
-    let new_y = y + dy;
+/// return the position of a tile left-clicked in player's FOV (optionally in a
+/// range), or None if right-clicked.
+fn target_tile(tcod: &mut Tcod, game: &GameState, max_range: Option<f32>) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::Escape;
+    loop {
+        // render the screen. this erases the inventory and shows the names of
+        // objects under the mouse.
+        tcod.root.flush();
+        let event = input::check_for_event(input::KEY_PRESS | input::MOUSE).map(|e| e.1);
+        match event {
+            Some(Event::Mouse(m)) => tcod.mouse = m,
+            Some(Event::Key(k)) => tcod.key = k,
+            None => tcod.key = Default::default(),
+        }
+        tcod.con.clear();
+        render_all(tcod, game);
 
-    // try to find an attackable object there
-    let target_id = objects
-        .iter()
-        .position(|object| object.fighter.is_some() && object.get_pos() == (new_x, new_y));
+        let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
 
-    // attack if target found, move otherwise
-    match target_id {
-        Some(target_id) => {
-            let (player, target) = mut_two(PLAYER_IDX, target_id, objects);
-            player.attack(target, game);
+        // accept the target if the player clicked in FOV, and in case a range
+        // is specified, if it's in that range
+        if tcod.mouse.lbutton_pressed && item::in_range(x, y, max_range, game) {
+            return Some((x, y));
         }
-        None => {
-            move_by(PLAYER_IDX, dx, dy, &game.map, objects);
+
+        if tcod.mouse.rbutton_pressed || tcod.key.code == Escape {
+            return None; // cancel if the player right-clicked or pressed Escape
         }
     }
 }
 
-fn render_all(tcod: &mut Tcod, objects: &[Object], game: &mut Game, fov_recompute: bool) {
-    //If we're recomputing fov, first get the player's line of sight,
-    //and use that to mark whether a tile is visible or not.
-    //Use this to color everything the default shade factor also
-
-    //Next for each object , for every point within the light's raduis, check to see if it's visible.
-    //If it is calculate the shade factor from the original objects light, and apply it if it's stronger(?)
-    if fov_recompute {
-        let (x, y) = objects[0].get_pos();
-        game.map.refresh_visibility(x, y);
-
-        for obj in objects {
-            if let Some(light) = &obj.light {
-                let (x, y) = obj.get_pos();
-                game.map.light_tiles(x, y, light)
+//The inventory is shown over the map, which is left as it was drawn this frame
+fn inventory_menu(
+    inventory: &[Object],
+    header: &str,
+    con: &mut Offscreen,
+    root: &mut Root,
+) -> Option<usize> {
+    // show a menu with each item of the inventory as an option
+    let options = if inventory.is_empty() {
+        vec!["Inventory is empty.".into()]
+    } else {
+        inventory
+            .iter()
+            .map(|item| {
+                let mut name = item.name.clone();
+                if let Some(equipment) = item.equipment {
+                    if equipment.equipped {
+                        name = format!("{} (on {})", name, equipment.slot);
+                    }
+                    if let Some(fuel) = equipment.fuel {
+                        name = format!("{} [fuel: {}]", name, fuel);
+                    }
+                }
+                name
+            })
+            .collect()
+    };
+
+    let inventory_index = menu_over(header, &options, INVENTORY_WIDTH, con, root);
+
+    // if an item was chosen, return it
+    if !inventory.is_empty() {
+        inventory_index
+    } else {
+        None
+    }
+}
+
+fn render_all(tcod: &mut Tcod, game: &GameState) {
+    let objects = &game.objects;
+    // doors go down first, so anything standing in a doorway is drawn over them
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let tile = game.map.get_tile(x, y);
+            if let (true, Some(door)) = (tile.explored, tile.door_char()) {
+                let color = game.light_seen_at(x, y).tint(colors::SEPIA);
+                tcod.con.set_default_foreground(color.into());
+                tcod.con.put_char(x, y, door, BackgroundFlag::None);
             }
         }
     }
 
-    let mut to_draw: Vec<_> = objects.iter().filter(|o| game.map.is_in_fov(o)).collect();
+    let mut to_draw: Vec<_> = objects
+        .iter()
+        .filter(|o| {
+            let (x, y) = o.get_pos();
+            game.fov.is_in_fov(o) || (o.always_visible && game.map.get_tile(x, y).explored)
+        })
+        .collect();
     to_draw.sort_by(|o1, o2| o1.blocks.cmp(&o2.blocks));
     for object in to_draw {
         let (x, y) = object.get_pos();
-        let tile = game.map.map[x as usize][y as usize];
-        if tile.visible {
-            //object.draw(&mut tcod.con);
-            let color = shade_color(
-                object.color,
-                game.map.map[x as usize][y as usize].shade_factor,
-            );
-            tcod.con.set_default_foreground(color);
-            let (x, y) = object.get_pos();
-            tcod.con.put_char(x, y, object.char, BackgroundFlag::None);
-        }
+        // objects take on the color of the light falling on them
+        let color = game.light_seen_at(x, y).tint(object.color);
+        draw::draw(object, color, &mut tcod.con);
     }
 
     for y in 0..MAP_HEIGHT {
         for x in 0..MAP_WIDTH {
-            let visible = game.map.map[x as usize][y as usize].visible;
-
-            let color = shade_color(
-                game.map.map[x as usize][y as usize].color,
-                game.map.map[x as usize][y as usize].shade_factor,
-            );
+            let tile = game.map.get_tile(x, y);
+            let color = game.light_seen_at(x, y).tint(tile.color);
 
             //println!("color: {0}", color);
-            let explored = &mut game.map.map[x as usize][y as usize].explored;
-            if visible {
-                *explored = true;
-            }
-            if *explored {
+            if tile.explored {
                 tcod.con
-                    .set_char_background(x, y, color, BackgroundFlag::Set);
+                    .set_char_background(x, y, color.into(), BackgroundFlag::Set);
             }
         }
     }
     // show the player's stats
-    tcod.panel.set_default_background(colors::BLACK);
+    tcod.panel.set_default_background(colors::BLACK.into());
     tcod.panel.clear();
     let hp = objects[PLAYER_IDX].fighter.map_or(0, |f| f.hp);
-    let max_hp = objects[PLAYER_IDX].fighter.map_or(0, |f| f.max_hp);
+    let max_hp = game.max_hp(PLAYER_IDX);
     render_bar(
         &mut tcod.panel,
         1,
@@ -267,15 +523,69 @@ fn render_all(tcod: &mut Tcod, objects: &[Object], game: &mut Game, fov_recomput
         colors::LIGHT_RED,
         colors::DARKER_RED,
     );
+    let xp = objects[PLAYER_IDX].fighter.map_or(0, |f| f.xp);
+    tcod.panel.print_ex(
+        1,
+        2,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!(
+            "Level: {}  XP: {}/{}",
+            objects[PLAYER_IDX].level,
+            xp,
+            game.level_up_xp()
+        ),
+    );
+    tcod.panel.print_ex(
+        1,
+        3,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Dungeon level: {}", game.dungeon_level),
+    );
+    // how long the light the player is carrying has left
+    let fuel = game
+        .inventory
+        .iter()
+        .filter_map(|item| item.equipment)
+        .filter(|e| e.equipped && e.light.is_some())
+        .filter_map(|e| e.fuel)
+        .max();
+    if let Some(fuel) = fuel {
+        tcod.panel.print_ex(
+            1,
+            4,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            format!("Light: {} turns", fuel),
+        );
+    }
+    // how easily monsters can spot the player, from the light they're standing in
+    let detection_range = game.detection_range(PLAYER_IDX);
+    let (stealth, stealth_color) = if detection_range < 4.0 {
+        ("Hidden", colors::LIGHT_GREEN)
+    } else if detection_range < 8.0 {
+        ("Shadowed", colors::YELLOW)
+    } else {
+        ("Exposed", colors::LIGHT_RED)
+    };
+    tcod.panel.set_default_foreground(stealth_color.into());
+    tcod.panel.print_ex(
+        1,
+        5,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Stealth: {}", stealth),
+    );
 
     // display names of objects under the mouse
-    tcod.panel.set_default_foreground(colors::LIGHT_GREY);
+    tcod.panel.set_default_foreground(colors::LIGHT_GREY.into());
     tcod.panel.print_ex(
         1,
         0,
         BackgroundFlag::None,
         TextAlignment::Left,
-        get_names_under_mouse(tcod.mouse, objects, &game.map),
+        get_names_under_mouse(tcod.mouse, objects, &game.fov),
     );
 
     // print the game messages, one line at a time
@@ -286,7 +596,7 @@ fn render_all(tcod: &mut Tcod, objects: &[Object], game: &mut Game, fov_recomput
         if y < 0 {
             break;
         }
-        tcod.panel.set_default_foreground(color);
+        tcod.panel.set_default_foreground(color.into());
         tcod.panel.print_rect(MSG_X, y, MSG_WIDTH, 0, msg);
     }
 
@@ -312,41 +622,6 @@ fn render_all(tcod: &mut Tcod, objects: &[Object], game: &mut Game, fov_recomput
     tcod.root.flush();
 }
 
-fn shade_color(original: Color, shade_factor: f32) -> Color {
-    let mut color: Color = colors::WHITE;
-    color.r = ((original.r as f32) * (1.0 - shade_factor)) as u8;
-    color.g = ((original.g as f32) * (1.0 - shade_factor)) as u8;
-    color.b = ((original.b as f32) * (1.0 - shade_factor)) as u8;
-    return color;
-}
-
-fn ai_turn(monster_id: usize, game: &mut Game, objects: &mut [Object]) {
-    let (monster_x, monster_y) = objects[monster_id].get_pos();
-    let monster_tile = game.map.map[monster_x as usize][monster_y as usize];
-    if monster_tile.visible {
-        if objects[monster_id].distance_to(&objects[PLAYER_IDX]) >= 2.0 {
-            let (player_x, player_y) = objects[PLAYER_IDX].get_pos();
-            move_towards(monster_id, player_x, player_y, &game.map, objects);
-        } else if objects[PLAYER_IDX].fighter.map_or(false, |f| f.hp > 0) {
-            let (monster, player) = mut_two(monster_id, PLAYER_IDX, objects);
-            monster.attack(player, game);
-        }
-    }
-}
-
-/// Mutably borrow two *separate* elements from the given slice.
-/// Panics when the indexes are equal or out of bounds.
-fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
-    assert!(first_index != second_index);
-    let split_at_index = cmp::max(first_index, second_index);
-    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
-    if first_index < second_index {
-        (&mut first_slice[first_index], &mut second_slice[0])
-    } else {
-        (&mut second_slice[0], &mut first_slice[second_index])
-    }
-}
-
 fn render_bar(
     panel: &mut Offscreen,
     x: i32,
@@ -360,15 +635,15 @@ fn render_bar(
 ) {
     let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
 
-    panel.set_default_background((back_color));
+    panel.set_default_background((back_color.into()));
     panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
 
-    panel.set_default_background(bar_color);
+    panel.set_default_background(bar_color.into());
     if bar_width > 0 {
         panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
     }
 
-    panel.set_default_foreground(colors::WHITE);
+    panel.set_default_foreground(colors::WHITE.into());
     panel.print_ex(
         x + total_width / 2,
         y,
@@ -378,13 +653,13 @@ fn render_bar(
     );
 }
 
-fn get_names_under_mouse(mouse: Mouse, objects: &[Object], map: &Map) -> String {
+fn get_names_under_mouse(mouse: Mouse, objects: &[Object], fov: &FovMap) -> String {
     let (x, y) = (mouse.cx as i32, mouse.cy as i32);
 
     // create a list with the names of all objects at the mouse's coordinates and in FOV
     let names = objects
         .iter()
-        .filter(|obj| obj.get_pos() == (x, y) && map.is_in_fov(obj))
+        .filter(|obj| obj.get_pos() == (x, y) && fov.is_in_fov(obj))
         .map(|obj| obj.name.clone())
         .collect::<Vec<_>>();
 
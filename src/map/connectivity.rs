@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+
+use entities::object::Object;
+use map::map::Map;
+
+//Only straight steps count, so everything connected can be reached with the arrow keys alone
+const STEPS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+///
+/// Which floor tiles can be walked to from (x, y), indexed [x][y] like the map.
+/// Walls and anything outside the map are never reachable. Doors are, closed or not.
+///
+pub fn flood_fill(map: &Map, x: i32, y: i32) -> Vec<Vec<bool>> {
+    let mut reached = vec![vec![false; map.height as usize]; map.width as usize];
+    if !map.valid_point(x, y) || !map.get_tile(x, y).passable() {
+        return reached;
+    }
+
+    reached[x as usize][y as usize] = true;
+    let mut frontier = VecDeque::new();
+    frontier.push_back((x, y));
+    while let Some((x, y)) = frontier.pop_front() {
+        for &(dx, dy) in STEPS.iter() {
+            let (next_x, next_y) = (x + dx, y + dy);
+            if map.valid_point(next_x, next_y)
+                && map.get_tile(next_x, next_y).passable()
+                && !reached[next_x as usize][next_y as usize]
+            {
+                reached[next_x as usize][next_y as usize] = true;
+                frontier.push_back((next_x, next_y));
+            }
+        }
+    }
+    reached
+}
+
+///
+/// Splits the floor into regions that can't be walked between, largest first.
+///
+pub fn regions(map: &Map) -> Vec<Vec<(i32, i32)>> {
+    let mut regions: Vec<Vec<(i32, i32)>> = vec![];
+    let mut found = vec![vec![false; map.height as usize]; map.width as usize];
+    for x in 0..map.width {
+        for y in 0..map.height {
+            if found[x as usize][y as usize] || !map.get_tile(x, y).passable() {
+                continue;
+            }
+            let reached = flood_fill(map, x, y);
+            let region = tiles_where(map, |x, y| reached[x as usize][y as usize]);
+            for &(x, y) in region.iter() {
+                found[x as usize][y as usize] = true;
+            }
+            regions.push(region);
+        }
+    }
+    regions.sort_by_key(|region| -(region.len() as i32));
+    regions
+}
+
+//Floor regions the player can't get to from start
+pub fn unreachable_regions(map: &Map, start: (i32, i32)) -> Vec<Vec<(i32, i32)>> {
+    regions(map)
+        .into_iter()
+        .filter(|region| !region.contains(&start))
+        .collect()
+}
+
+///
+/// Digs a tunnel from each region cut off from start to the closest tile that isn't,
+/// until every floor tile can be reached.
+///
+pub fn connect_regions(map: &mut Map, start: (i32, i32)) {
+    loop {
+        let reached = flood_fill(map, start.0, start.1);
+        match shortest_tunnel(map, &reached) {
+            Some(tunnel) => {
+                for &(x, y) in tunnel.iter() {
+                    map.carve(x, y);
+                }
+            }
+            None => return,
+        }
+    }
+}
+
+///
+/// The walls to dig through to join the nearest cut off floor tile to the reached ones.
+/// Searches outwards from every reached tile at once, walls included, so the first floor
+/// tile found that wasn't reached is the closest, and the way back to it is the tunnel.
+///
+fn shortest_tunnel(map: &Map, reached: &[Vec<bool>]) -> Option<Vec<(i32, i32)>> {
+    let mut seen = reached.to_vec();
+    let mut came_from: Vec<Vec<Option<(i32, i32)>>> =
+        vec![vec![None; map.height as usize]; map.width as usize];
+    let mut frontier: VecDeque<(i32, i32)> =
+        tiles_where(map, |x, y| reached[x as usize][y as usize])
+            .into_iter()
+            .collect();
+
+    while let Some((x, y)) = frontier.pop_front() {
+        for &(dx, dy) in STEPS.iter() {
+            let (next_x, next_y) = (x + dx, y + dy);
+            if !map.valid_point(next_x, next_y) || seen[next_x as usize][next_y as usize] {
+                continue;
+            }
+            seen[next_x as usize][next_y as usize] = true;
+            came_from[next_x as usize][next_y as usize] = Some((x, y));
+
+            if !map.get_tile(next_x, next_y).passable() {
+                frontier.push_back((next_x, next_y));
+                continue;
+            }
+
+            // walk back to the reached tile the search came from, digging out the walls
+            let mut tunnel = vec![];
+            let mut current = (x, y);
+            while !reached[current.0 as usize][current.1 as usize] {
+                tunnel.push(current);
+                current = came_from[current.0 as usize][current.1 as usize].unwrap();
+            }
+            return Some(tunnel);
+        }
+    }
+    None
+}
+
+///
+/// Indices of the objects standing somewhere that can't be walked to from start,
+/// such as a monster walled in or stairs out of reach.
+///
+pub fn unreachable_objects(map: &Map, start: (i32, i32), objects: &[Object]) -> Vec<usize> {
+    let reached = flood_fill(map, start.0, start.1);
+    objects
+        .iter()
+        .enumerate()
+        .filter(|&(_, object)| {
+            let (x, y) = object.get_pos();
+            !map.valid_point(x, y) || !reached[x as usize][y as usize]
+        })
+        .map(|(id, _)| id)
+        .collect()
+}
+
+fn tiles_where<F: Fn(i32, i32) -> bool>(map: &Map, include: F) -> Vec<(i32, i32)> {
+    let mut tiles = vec![];
+    for x in 0..map.width {
+        for y in 0..map.height {
+            if include(x, y) {
+                tiles.push((x, y));
+            }
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colors;
+    use map::generator::Generator;
+    use rand::{Isaac64Rng, SeedableRng};
+
+    fn monster(x: i32, y: i32) -> Object {
+        Object::new(x, y, 'o', "orc", colors::WHITE, true)
+    }
+
+    #[test]
+    fn finds_rooms_cut_off_from_the_start() {
+        let map = Map::from_rows(&[
+            "###########",
+            "#...#.....#",
+            "#...#.....#",
+            "#...###.###",
+            "#...#.....#",
+            "###########",
+        ]);
+        assert_eq!(regions(&map).len(), 2);
+        let unreachable = unreachable_regions(&map, (1, 1));
+        assert_eq!(unreachable.len(), 1);
+        assert_eq!(unreachable[0].len(), 16);
+        assert!(unreachable[0].contains(&(7, 3)));
+    }
+
+    #[test]
+    fn diagonal_gaps_do_not_connect() {
+        let map = Map::from_rows(&["######", "#..###", "#..###", "###..#", "###..#", "######"]);
+        assert_eq!(unreachable_regions(&map, (1, 1)).len(), 1);
+    }
+
+    #[test]
+    fn connecting_leaves_nothing_out_of_reach() {
+        let mut map = Map::from_rows(&[
+            "##############",
+            "#..#####..####",
+            "#..#####..####",
+            "##########..##",
+            "#.#########..#",
+            "##############",
+        ]);
+        assert_eq!(unreachable_regions(&map, (1, 1)).len(), 3);
+        connect_regions(&mut map, (1, 1));
+        assert!(unreachable_regions(&map, (1, 1)).is_empty());
+    }
+
+    #[test]
+    fn connecting_digs_the_shortest_tunnel() {
+        let mut map = Map::from_rows(&[
+            "##########",
+            "#..##....#",
+            "#..##....#",
+            "######...#",
+            "#.########",
+            "##########",
+        ]);
+        let floor = |map: &Map| tiles_where(map, |x, y| map.get_tile(x, y).passable()).len();
+        let before = floor(&map);
+        connect_regions(&mut map, (1, 1));
+        assert!(unreachable_regions(&map, (1, 1)).is_empty());
+        // two walls between the first rooms, then one under the top left room
+        assert_eq!(floor(&map), before + 3);
+    }
+
+    #[test]
+    fn walled_in_monsters_are_reported() {
+        let map = Map::from_rows(&["#########", "#....#..#", "#....#..#", "#########"]);
+        let objects = vec![monster(1, 1), monster(4, 2), monster(6, 1), monster(7, 2)];
+        assert_eq!(unreachable_objects(&map, (1, 1), &objects), vec![2, 3]);
+    }
+
+    #[test]
+    fn generated_levels_have_everything_in_reach() {
+        let generators = [
+            Generator::RoomsAndCorridors,
+            Generator::Bsp,
+            Generator::Caves,
+            Generator::DrunkardsWalk,
+        ];
+        for &generator in generators.iter() {
+            for seed in 0..20 {
+                let mut map = Map::new(80, 43);
+                let player = Object::new(0, 0, '@', "player", colors::WHITE, true);
+                let mut rng = Isaac64Rng::from_seed(&[seed, 1]);
+                let objects = map.make_rand_map(player, 1, generator, &mut rng);
+                let start = objects[0].get_pos();
+
+                assert!(
+                    unreachable_regions(&map, start).is_empty(),
+                    "{} level from seed {} has floor out of reach",
+                    generator,
+                    seed
+                );
+                assert!(
+                    unreachable_objects(&map, start, &objects).is_empty(),
+                    "{} level from seed {} has objects out of reach",
+                    generator,
+                    seed
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use map::map::Map;
+use map::tile::Tile;
+use map::NEIGHBOURS;
+
+//How strongly a flee map pushes away from its goals. Values past -1 make
+//fleeing objects prefer long escape routes over cowering in the nearest corner
+const FLEE_COEFFICIENT_TENTHS: i32 = -12;
+
+///
+/// The cost of walking onto a tile for anything that can't pass through walls.
+/// Closed doors take an extra turn, to open them first, and can't be walked onto at all
+/// by anything that doesn't open doors.
+///
+pub fn walk_cost(tile: &Tile, opens_doors: bool) -> Option<i32> {
+    if tile.is_closed_door() {
+        if opens_doors {
+            Some(2)
+        } else {
+            None
+        }
+    } else if tile.blocked {
+        None
+    } else {
+        Some(1)
+    }
+}
+
+///
+/// Distance from every tile of a map to the nearest of a set of goals.
+/// Anything can find its way to the closest goal by rolling downhill.
+///
+pub struct DijkstraMap {
+    width: i32,
+    height: i32,
+    values: Vec<Option<i32>>,
+}
+
+impl DijkstraMap {
+    ///
+    /// Builds the map outward from all goals at once. cost gives the price of stepping
+    /// onto a tile, or None if it can't be entered at all.
+    ///
+    pub fn new<F>(map: &Map, goals: &[(i32, i32)], cost: F) -> Self
+    where
+        F: Fn(&Tile) -> Option<i32>,
+    {
+        let seeds: Vec<_> = goals
+            .iter()
+            .filter(|&&(x, y)| map.valid_point(x, y))
+            .map(|&goal| (goal, 0))
+            .collect();
+        DijkstraMap::scan(map, seeds, cost)
+    }
+
+    ///
+    /// A map for running away from this map's goals: downhill leads away from them,
+    /// preferring routes that keep going over dead ends.
+    ///
+    pub fn inverted<F>(&self, map: &Map, cost: F) -> Self
+    where
+        F: Fn(&Tile) -> Option<i32>,
+    {
+        let mut seeds = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(value) = self.value(x, y) {
+                    seeds.push(((x, y), value * FLEE_COEFFICIENT_TENTHS / 10));
+                }
+            }
+        }
+        DijkstraMap::scan(map, seeds, cost)
+    }
+
+    /// None if no goal can be reached from the tile
+    pub fn value(&self, x: i32, y: i32) -> Option<i32> {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        self.values[(x + y * self.width) as usize]
+    }
+
+    ///
+    /// The neighbouring tile with the lowest value, if any is lower than the tile itself.
+    ///
+    pub fn downhill(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let mut best = self.value(x, y)?;
+        let mut best_pos = None;
+        for &(dx, dy) in NEIGHBOURS.iter() {
+            if let Some(value) = self.value(x + dx, y + dy) {
+                if value < best {
+                    best = value;
+                    best_pos = Some((x + dx, y + dy));
+                }
+            }
+        }
+        best_pos
+    }
+
+    //Plain Dijkstra, but every seed tile starts with its own value instead of zero
+    fn scan<F>(map: &Map, seeds: Vec<((i32, i32), i32)>, cost: F) -> Self
+    where
+        F: Fn(&Tile) -> Option<i32>,
+    {
+        let index = |x: i32, y: i32| (x + y * map.width) as usize;
+        let mut values = vec![None; (map.width * map.height) as usize];
+        let mut frontier = BinaryHeap::new();
+
+        for ((x, y), value) in seeds {
+            values[index(x, y)] = Some(value);
+            frontier.push(Reverse((value, (x, y))));
+        }
+
+        while let Some(Reverse((value, (x, y)))) = frontier.pop() {
+            if values[index(x, y)].map_or(false, |best| best < value) {
+                continue;
+            }
+            for &(dx, dy) in NEIGHBOURS.iter() {
+                let (next_x, next_y) = (x + dx, y + dy);
+                if !map.valid_point(next_x, next_y) {
+                    continue;
+                }
+                let step_cost = match cost(&map.map[next_x as usize][next_y as usize]) {
+                    Some(step_cost) => step_cost,
+                    None => continue,
+                };
+                let new_value = value + step_cost;
+                if values[index(next_x, next_y)].map_or(true, |old| new_value < old) {
+                    values[index(next_x, next_y)] = Some(new_value);
+                    frontier.push(Reverse((new_value, (next_x, next_y))));
+                }
+            }
+        }
+
+        DijkstraMap {
+            width: map.width,
+            height: map.height,
+            values,
+        }
+    }
+}
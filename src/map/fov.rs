@@ -0,0 +1,566 @@
+use std::fmt;
+
+use map::fov_map::FovMap;
+use map::map::Map;
+use map::shadow_line::{Shadow, ShadowLine};
+
+///
+/// A way of working out what can be seen from a point.
+///
+pub trait Fov {
+    /// Marks everything visible from (x, y), out to radius, in fov. fov is not cleared first.
+    fn compute(&self, map: &Map, x: i32, y: i32, radius: i32, fov: &mut FovMap);
+}
+
+//The FOV implementations, so one can be picked (and saved) at runtime
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FovAlgorithm {
+    ShadowLine,
+    Permissive,
+    Symmetric,
+    Raycasting,
+}
+
+impl FovAlgorithm {
+    pub fn implementation(self) -> &'static dyn Fov {
+        use self::FovAlgorithm::*;
+        match self {
+            ShadowLine => &ShadowCasting,
+            Permissive => &PermissiveFov,
+            Symmetric => &SymmetricShadowCasting,
+            Raycasting => &RayCasting,
+        }
+    }
+
+    //Cycles through every algorithm, for comparing them in game
+    pub fn next(self) -> FovAlgorithm {
+        use self::FovAlgorithm::*;
+        match self {
+            ShadowLine => Permissive,
+            Permissive => Symmetric,
+            Symmetric => Raycasting,
+            Raycasting => ShadowLine,
+        }
+    }
+}
+
+impl fmt::Display for FovAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::FovAlgorithm::*;
+        match *self {
+            ShadowLine => write!(f, "shadowcasting"),
+            Permissive => write!(f, "permissive"),
+            Symmetric => write!(f, "symmetric shadowcasting"),
+            Raycasting => write!(f, "raycasting"),
+        }
+    }
+}
+
+//Whether an offset from the viewer is inside a circle of the given radius
+fn in_radius(dx: i32, dy: i32, radius: i32) -> bool {
+    let (dx, dy, radius) = (dx as i64, dy as i64, radius as i64);
+    dx * dx + dy * dy <= radius * radius
+}
+
+///
+/// Shadowcasting with a ShadowLine of the shadows cast so far, one octant at a time.
+/// Its tile projections aren't the same seen from either end, so a monster can be in
+/// view without being able to see back, and pillars leave ragged gaps behind them.
+///
+pub struct ShadowCasting;
+
+impl Fov for ShadowCasting {
+    fn compute(&self, map: &Map, x: i32, y: i32, radius: i32, fov: &mut FovMap) {
+        for octant in 0..8 {
+            self.refresh_octant(map, x, y, octant, radius, fov);
+        }
+    }
+}
+
+impl ShadowCasting {
+    //Each octant represents 45 degrees. Stops after radius rows, or at the edge of the map
+    fn refresh_octant(
+        &self,
+        map: &Map,
+        x: i32,
+        y: i32,
+        octant: i32,
+        radius: i32,
+        fov: &mut FovMap,
+    ) {
+        let mut line = ShadowLine::new();
+        let mut full_shadow = false;
+        let mut row = 1;
+
+        while row <= radius && !full_shadow {
+            let (x_transform, y_transform) = self.transform_octant(row, 0, octant);
+            let (pos_x, pos_y) = (x + x_transform, y + y_transform);
+
+            if !map.valid_point(pos_x, pos_y) {
+                break;
+            }
+            for col in 0..row + 1 {
+                //if out of bounds, break
+                let (x_transform, y_transform) = self.transform_octant(row, col, octant);
+                let (pos_x, pos_y) = (x + x_transform, y + y_transform);
+
+                if !map.valid_point(pos_x, pos_y) {
+                    break;
+                }
+
+                let projection = self.project_tile(row as f32, col as f32);
+                if !line.is_in_shadow(&projection) {
+                    if in_radius(x_transform, y_transform, radius) {
+                        fov.set_visible(pos_x, pos_y);
+                    }
+                    if map.blocks_sight(pos_x, pos_y) {
+                        line.add(projection);
+                        full_shadow = line.is_full_shadow();
+                    }
+                }
+            }
+            row = row + 1;
+        }
+    }
+
+    fn project_tile(&self, row: f32, col: f32) -> Shadow {
+        let top_left = col / (row + 2.0);
+        let bottom_right = (col + 1.0) / (row + 1.0);
+        Shadow::new(top_left, bottom_right)
+    }
+
+    // takes in a point in 2d spaces, and transforms it to the relevant octant.
+    fn transform_octant(&self, row: i32, col: i32, octant: i32) -> (i32, i32) {
+        match octant {
+            0 => return (col, -row),
+            1 => return (row, -col),
+            2 => return (row, col),
+            3 => return (col, row),
+            4 => return (-col, row),
+            5 => return (-row, col),
+            6 => return (-row, -col),
+            _ => return (-col, -row),
+        }
+    }
+}
+
+///
+/// A tile is visible if a clear line runs from the viewer's center to its center,
+/// or to any point near one of its corners. Sees more of walls and around pillars.
+///
+pub struct PermissiveFov;
+
+//How far from a tile's center the corner points of a permissive line are
+const CORNER_OFFSET: f32 = 0.4;
+
+impl Fov for PermissiveFov {
+    fn compute(&self, map: &Map, x: i32, y: i32, radius: i32, fov: &mut FovMap) {
+        let targets = [
+            (0.0, 0.0),
+            (-CORNER_OFFSET, -CORNER_OFFSET),
+            (CORNER_OFFSET, -CORNER_OFFSET),
+            (-CORNER_OFFSET, CORNER_OFFSET),
+            (CORNER_OFFSET, CORNER_OFFSET),
+        ];
+        let reach = radius.min(map.width.max(map.height));
+        for target_x in x - reach..=x + reach {
+            for target_y in y - reach..=y + reach {
+                if !map.valid_point(target_x, target_y)
+                    || !in_radius(target_x - x, target_y - y, radius)
+                {
+                    continue;
+                }
+                let seen = targets.iter().any(|&(offset_x, offset_y)| {
+                    self.is_clear(
+                        map,
+                        (x, y),
+                        (target_x, target_y),
+                        (target_x as f32 + offset_x, target_y as f32 + offset_y),
+                    )
+                });
+                if seen {
+                    fov.set_visible(target_x, target_y);
+                }
+            }
+        }
+    }
+}
+
+impl PermissiveFov {
+    //Walks the line in small steps, checking every tile it passes through on the way
+    fn is_clear(&self, map: &Map, from: (i32, i32), to: (i32, i32), point: (f32, f32)) -> bool {
+        let (dx, dy) = (point.0 - from.0 as f32, point.1 - from.1 as f32);
+        let steps = ((dx.abs().max(dy.abs())) * 4.0).ceil() as i32;
+        for step in 1..steps {
+            let t = step as f32 / steps as f32;
+            let tile = (
+                (from.0 as f32 + dx * t).round() as i32,
+                (from.1 as f32 + dy * t).round() as i32,
+            );
+            if tile != from && tile != to && map.blocks_sight(tile.0, tile.1) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+///
+/// Symmetric shadowcasting: if A can see B, then B can see A. Floor tiles are only
+/// visible if their center is in view, walls if any part of them is.
+///
+pub struct SymmetricShadowCasting;
+
+//A slope from the viewer as a fraction, so tiles on the edge of a shadow are exact
+#[derive(Clone, Copy, Debug)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    fn min_col(&self) -> i32 {
+        // round ties up
+        floor_div(
+            2 * self.depth * self.start.num + self.start.den,
+            2 * self.start.den,
+        )
+    }
+
+    fn max_col(&self) -> i32 {
+        // round ties down
+        -floor_div(
+            -(2 * self.depth * self.end.num - self.end.den),
+            2 * self.end.den,
+        )
+    }
+
+    fn next(&self) -> Row {
+        Row {
+            depth: self.depth + 1,
+            start: self.start,
+            end: self.end,
+        }
+    }
+
+    //Whether the center of the tile at col lies between the row's slopes
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+}
+
+fn floor_div(a: i32, b: i32) -> i32 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+impl Fov for SymmetricShadowCasting {
+    fn compute(&self, map: &Map, x: i32, y: i32, radius: i32, fov: &mut FovMap) {
+        for quadrant in 0..4 {
+            let first_row = Row {
+                depth: 1,
+                start: Slope { num: -1, den: 1 },
+                end: Slope { num: 1, den: 1 },
+            };
+            self.scan(map, (x, y), quadrant, first_row, radius, fov);
+        }
+    }
+}
+
+impl SymmetricShadowCasting {
+    fn scan(
+        &self,
+        map: &Map,
+        origin: (i32, i32),
+        quadrant: i32,
+        mut row: Row,
+        radius: i32,
+        fov: &mut FovMap,
+    ) {
+        if row.depth > radius {
+            return;
+        }
+        let mut prev_wall = None;
+        for col in row.min_col()..=row.max_col() {
+            let (x, y) = self.transform(origin, quadrant, row.depth, col);
+            let wall = map.blocks_sight(x, y);
+            if (wall || row.is_symmetric(col)) && in_radius(x - origin.0, y - origin.1, radius) {
+                fov.set_visible(x, y);
+            }
+            if prev_wall == Some(true) && !wall {
+                row.start = Slope {
+                    num: 2 * col - 1,
+                    den: 2 * row.depth,
+                };
+            }
+            if prev_wall == Some(false) && wall {
+                let mut next_row = row.next();
+                next_row.end = Slope {
+                    num: 2 * col - 1,
+                    den: 2 * row.depth,
+                };
+                self.scan(map, origin, quadrant, next_row, radius, fov);
+            }
+            prev_wall = Some(wall);
+        }
+        if prev_wall == Some(false) {
+            self.scan(map, origin, quadrant, row.next(), radius, fov);
+        }
+    }
+
+    //Quadrants are north, east, south and west of the viewer
+    fn transform(&self, origin: (i32, i32), quadrant: i32, depth: i32, col: i32) -> (i32, i32) {
+        let (x, y) = origin;
+        match quadrant {
+            0 => (x + col, y - depth),
+            1 => (x + depth, y + col),
+            2 => (x + col, y + depth),
+            _ => (x - depth, y + col),
+        }
+    }
+}
+
+///
+/// Casts a straight ray to every tile on the edge of the view, stopping each at the first wall.
+/// Cheap, but leaves gaps and sees walls unevenly.
+///
+pub struct RayCasting;
+
+impl Fov for RayCasting {
+    fn compute(&self, map: &Map, x: i32, y: i32, radius: i32, fov: &mut FovMap) {
+        let reach = radius.min(map.width.max(map.height));
+        for edge in -reach..=reach {
+            self.cast_ray(map, (x, y), (x + edge, y - reach), radius, fov);
+            self.cast_ray(map, (x, y), (x + edge, y + reach), radius, fov);
+            self.cast_ray(map, (x, y), (x - reach, y + edge), radius, fov);
+            self.cast_ray(map, (x, y), (x + reach, y + edge), radius, fov);
+        }
+    }
+}
+
+impl RayCasting {
+    //Bresenham's line from the viewer towards target
+    fn cast_ray(
+        &self,
+        map: &Map,
+        from: (i32, i32),
+        target: (i32, i32),
+        radius: i32,
+        fov: &mut FovMap,
+    ) {
+        let (dx, dy) = ((target.0 - from.0).abs(), -(target.1 - from.1).abs());
+        let (step_x, step_y) = ((target.0 - from.0).signum(), (target.1 - from.1).signum());
+        let mut error = dx + dy;
+        let (mut x, mut y) = from;
+
+        while (x, y) != target {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+            if !map.valid_point(x, y) || !in_radius(x - from.0, y - from.1, radius) {
+                return;
+            }
+            fov.set_visible(x, y);
+            if map.blocks_sight(x, y) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Reads a fixture, where '@' marks the viewer, on floor
+    fn fixture(rows: &[&str]) -> (Map, (i32, i32)) {
+        let mut viewer = (0, 0);
+        for (y, row) in rows.iter().enumerate() {
+            if let Some(x) = row.find('@') {
+                viewer = (x as i32, y as i32);
+            }
+        }
+        (Map::from_rows(rows), viewer)
+    }
+
+    //Draws what was seen the same way as a fixture, leaving blanks for anything unseen
+    fn render(map: &Map, viewer: (i32, i32), fov: &FovMap) -> Vec<String> {
+        (0..map.height)
+            .map(|y| {
+                (0..map.width)
+                    .map(|x| {
+                        if (x, y) == viewer {
+                            '@'
+                        } else if !fov.is_visible(x, y) {
+                            ' '
+                        } else if map.blocks_sight(x, y) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn symmetric_view(rows: &[&str], radius: i32) -> Vec<String> {
+        let (map, viewer) = fixture(rows);
+        let fov = map.field_of_view(viewer.0, viewer.1, radius, FovAlgorithm::Symmetric);
+        render(&map, viewer, &fov)
+    }
+
+    #[test]
+    fn sees_all_of_an_open_room_and_its_walls() {
+        let room = [
+            "#########",
+            "#.......#",
+            "#.@.....#",
+            "#.......#",
+            "#########",
+        ];
+        assert_eq!(symmetric_view(&room, 20), room);
+    }
+
+    #[test]
+    fn pillar_casts_an_even_shadow() {
+        let room = [
+            "###########",
+            "#.........#",
+            "#.........#",
+            "#..@.#....#",
+            "#.........#",
+            "#.........#",
+            "###########",
+        ];
+        let expected = [
+            "###########",
+            "#.........#",
+            "#.......   ",
+            "#..@.#     ",
+            "#.......   ",
+            "#.........#",
+            "###########",
+        ];
+        assert_eq!(symmetric_view(&room, 20), expected);
+    }
+
+    #[test]
+    fn corridor_only_shows_what_is_straight_ahead() {
+        let corridor = [
+            "#####.#####",
+            "#####.#####",
+            "#####@#####",
+            "#####.#####",
+            "#.........#",
+            "#.........#",
+            "#.........#",
+            "###########",
+        ];
+        let expected = [
+            "    #.#    ",
+            "    #.#    ",
+            "    #@#    ",
+            "    #.#    ",
+            "    ...    ",
+            "    ...    ",
+            "   .....   ",
+            "   #####   ",
+        ];
+        assert_eq!(symmetric_view(&corridor, 20), expected);
+    }
+
+    #[test]
+    fn closed_doors_block_sight_until_opened() {
+        let rooms = [
+            "#########",
+            "#..#....#",
+            "#.@+....#",
+            "#..#....#",
+            "#########",
+        ];
+        let (mut map, viewer) = fixture(&rooms);
+        let closed = map.field_of_view(viewer.0, viewer.1, 20, FovAlgorithm::Symmetric);
+        assert!(closed.is_visible(3, 2));
+        assert!(!closed.is_visible(5, 2));
+
+        map.map[3][2].open_door();
+        let open = map.field_of_view(viewer.0, viewer.1, 20, FovAlgorithm::Symmetric);
+        assert!(open.is_visible(5, 2));
+        assert!(open.is_visible(7, 2));
+    }
+
+    #[test]
+    fn nothing_is_seen_past_the_radius() {
+        let room = [
+            "###########",
+            "#.........#",
+            "#.........#",
+            "#....@....#",
+            "#.........#",
+            "#.........#",
+            "###########",
+        ];
+        let expected = [
+            "     #     ",
+            "   .....   ",
+            "   .....   ",
+            "  ...@...  ",
+            "   .....   ",
+            "   .....   ",
+            "     #     ",
+        ];
+        assert_eq!(symmetric_view(&room, 3), expected);
+    }
+
+    #[test]
+    fn floor_tiles_see_each_other_both_ways() {
+        let (map, _) = fixture(&[
+            "##############",
+            "#....#.......#",
+            "#.##....#.#..#",
+            "#.#..##......#",
+            "#......#..##.#",
+            "##.#.......#.#",
+            "#....#.#.....#",
+            "##############",
+        ]);
+        let floors: Vec<(i32, i32)> = (0..map.width)
+            .flat_map(|x| (0..map.height).map(move |y| (x, y)))
+            .filter(|&(x, y)| !map.blocks_sight(x, y))
+            .collect();
+        let views: Vec<FovMap> = floors
+            .iter()
+            .map(|&(x, y)| map.field_of_view(x, y, 20, FovAlgorithm::Symmetric))
+            .collect();
+
+        for (a, view_from_a) in floors.iter().zip(views.iter()) {
+            for (b, view_from_b) in floors.iter().zip(views.iter()) {
+                assert_eq!(
+                    view_from_a.is_visible(b.0, b.1),
+                    view_from_b.is_visible(a.0, a.1),
+                    "{:?} and {:?} disagree on seeing each other",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
@@ -1,13 +1,48 @@
-mod map;
-
-use map::Map;
+use entities::object::Object;
 
+///
+/// What a single viewer can see of a map. Kept apart from the tiles so the player,
+/// monsters and lights can each have their own without clobbering each other.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FovMap {
-    pub map: Map
+    width: i32,
+    height: i32,
+    visible: Vec<bool>,
 }
 
 impl FovMap {
-    pub fn new() -> self{
+    pub fn new(width: i32, height: i32) -> Self {
+        FovMap {
+            width,
+            height,
+            visible: vec![false; (width * height) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for visible in self.visible.iter_mut() {
+            *visible = false;
+        }
+    }
+
+    pub fn set_visible(&mut self, x: i32, y: i32) {
+        if self.contains(x, y) {
+            self.visible[(x + y * self.width) as usize] = true;
+        }
+    }
 
+    /// false for anything outside the map
+    pub fn is_visible(&self, x: i32, y: i32) -> bool {
+        self.contains(x, y) && self.visible[(x + y * self.width) as usize]
     }
-}   
\ No newline at end of file
+
+    pub fn is_in_fov(&self, object: &Object) -> bool {
+        let (x, y) = object.get_pos();
+        self.is_visible(x, y)
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+}
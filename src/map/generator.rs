@@ -0,0 +1,497 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use map::connectivity::regions;
+use map::dijkstra::walk_cost;
+use map::map::Map;
+use map::rect::Rect;
+
+//rooms and corridors
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MAX_ROOMS: i32 = 30;
+
+//BSP leaves are never split smaller than this
+const BSP_MIN_LEAF: i32 = 10;
+
+//cellular automata caves
+const CAVE_WALL_CHANCE: f32 = 0.45;
+const CAVE_SMOOTHING_PASSES: i32 = 5;
+//A tile turns to wall when at least this many of the 9 tiles around it (itself included) are walls
+const CAVE_WALL_NEIGHBOURS: usize = 5;
+
+//drunkard's walk
+const DRUNKARD_FLOOR_FRACTION: f32 = 0.4;
+//Steps before the walker wanders back to somewhere it has already dug
+const DRUNKARD_STEPS: i32 = 200;
+
+//Open levels are split into squares this size to spread monsters and items around
+const AREA_SIZE: i32 = 10;
+
+///
+/// Where things go on a level once its walls are carved out.
+///
+pub struct Layout {
+    pub start: (i32, i32), //Where the player arrives
+    pub exit: (i32, i32),  //Where the stairs down go
+    pub areas: Vec<Rect>,  //Places to fill with monsters and items. Never where the player starts
+    pub doors: Vec<(i32, i32)>,
+}
+
+///
+/// A way of carving out a level. The map starts out as solid wall.
+///
+pub trait MapGenerator {
+    fn generate<R: Rng>(&self, map: &mut Map, rng: &mut R) -> Layout;
+}
+
+//The generators, so levels can pick one (and saves remember the pick)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Generator {
+    RoomsAndCorridors,
+    Bsp,
+    Caves,
+    DrunkardsWalk,
+}
+
+impl Generator {
+    pub fn generate<R: Rng>(self, map: &mut Map, rng: &mut R) -> Layout {
+        use self::Generator::*;
+        match self {
+            RoomsAndCorridors => RoomsAndCorridorsGenerator.generate(map, rng),
+            Bsp => BspGenerator.generate(map, rng),
+            Caves => CaveGenerator.generate(map, rng),
+            DrunkardsWalk => DrunkardsWalkGenerator.generate(map, rng),
+        }
+    }
+
+    //The generator used for a dungeon level, starting with plain rooms and then taking turns
+    pub fn for_level(level: u32) -> Generator {
+        use self::Generator::*;
+        let generators = [RoomsAndCorridors, Bsp, DrunkardsWalk, Caves];
+        generators[(level.max(1) as usize - 1) % generators.len()]
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Generator::*;
+        match *self {
+            RoomsAndCorridors => write!(f, "rooms"),
+            Bsp => write!(f, "bsp"),
+            Caves => write!(f, "caves"),
+            DrunkardsWalk => write!(f, "drunkard"),
+        }
+    }
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        use self::Generator::*;
+        [RoomsAndCorridors, Bsp, Caves, DrunkardsWalk]
+            .iter()
+            .cloned()
+            .find(|generator| generator.to_string() == name)
+            .ok_or_else(|| format!("Unknown map generator: {}", name))
+    }
+}
+
+///
+/// Up to MAX_ROOMS rectangles dropped at random, skipping any that overlap,
+/// each joined to the one before by an L-shaped tunnel.
+///
+pub struct RoomsAndCorridorsGenerator;
+
+impl MapGenerator for RoomsAndCorridorsGenerator {
+    fn generate<R: Rng>(&self, map: &mut Map, rng: &mut R) -> Layout {
+        let mut rooms: Vec<Rect> = vec![];
+        for _ in 0..MAX_ROOMS {
+            let w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+            let h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+            if w >= map.width || h >= map.height {
+                continue;
+            }
+            // random position without going out of the boundaries of the map
+            let x = rng.gen_range(0, map.width - w);
+            let y = rng.gen_range(0, map.height - h);
+
+            let new_room = Rect::new(x, y, w, h);
+            let failed = rooms
+                .iter()
+                .any(|other_room| new_room.intersects_with(other_room));
+
+            if !failed {
+                map.create_room(new_room);
+                // all rooms after the first are connected to the previous room
+                if let Some(prev_room) = rooms.last() {
+                    tunnel_between(map, prev_room.center(), new_room.center(), rng);
+                }
+                rooms.push(new_room);
+            }
+        }
+        rooms_layout(map, rooms, rng)
+    }
+}
+
+///
+/// Splits the map in two over and over, puts a room in each piece that's left,
+/// then joins the pieces back up in the order they were split.
+///
+pub struct BspGenerator;
+
+impl MapGenerator for BspGenerator {
+    fn generate<R: Rng>(&self, map: &mut Map, rng: &mut R) -> Layout {
+        let mut rooms = vec![];
+        // the last row and column stay solid, as rooms never carve their far edge
+        let whole_map = Rect::new(0, 0, map.width - 1, map.height - 1);
+        self.split(map, whole_map, rng, &mut rooms);
+        rooms_layout(map, rooms, rng)
+    }
+}
+
+impl BspGenerator {
+    fn split<R: Rng>(&self, map: &mut Map, leaf: Rect, rng: &mut R, rooms: &mut Vec<Rect>) {
+        let (w, h) = (leaf.x2 - leaf.x1, leaf.y2 - leaf.y1);
+        // cut across the longer side, so pieces don't end up as thin strips
+        let split_vertically = if w * 4 > h * 5 {
+            true
+        } else if h * 4 > w * 5 {
+            false
+        } else {
+            rng.gen()
+        };
+        let size = if split_vertically { w } else { h };
+
+        if size < BSP_MIN_LEAF * 2 {
+            // a thin leaf may still be too narrow for a room, whichever way it was cut
+            if w < ROOM_MIN_SIZE || h < ROOM_MIN_SIZE {
+                return;
+            }
+            let room_w = rng.gen_range(ROOM_MIN_SIZE, w + 1);
+            let room_h = rng.gen_range(ROOM_MIN_SIZE, h + 1);
+            let x = rng.gen_range(leaf.x1, leaf.x2 - room_w + 1);
+            let y = rng.gen_range(leaf.y1, leaf.y2 - room_h + 1);
+            let room = Rect::new(x, y, room_w, room_h);
+            map.create_room(room);
+            rooms.push(room);
+            return;
+        }
+
+        let at = rng.gen_range(BSP_MIN_LEAF, size - BSP_MIN_LEAF + 1);
+        let (first, second) = if split_vertically {
+            (
+                Rect::new(leaf.x1, leaf.y1, at, h),
+                Rect::new(leaf.x1 + at, leaf.y1, w - at, h),
+            )
+        } else {
+            (
+                Rect::new(leaf.x1, leaf.y1, w, at),
+                Rect::new(leaf.x1, leaf.y1 + at, w, h - at),
+            )
+        };
+        self.split(map, first, rng, rooms);
+        let first_of_second = rooms.len();
+        self.split(map, second, rng, rooms);
+
+        // the last room of one half is next to the first room of the other, if both got one
+        if first_of_second > 0 && rooms.len() > first_of_second {
+            let from = rooms[first_of_second - 1].center();
+            let to = rooms[first_of_second].center();
+            tunnel_between(map, from, to, rng);
+        }
+    }
+}
+
+///
+/// Random noise smoothed out into caves by cellular automata: a tile becomes wall
+/// if most of its neighbours are. Pockets cut off from the main cave are filled back in.
+///
+pub struct CaveGenerator;
+
+impl MapGenerator for CaveGenerator {
+    fn generate<R: Rng>(&self, map: &mut Map, rng: &mut R) -> Layout {
+        let (width, height) = (map.width, map.height);
+        let is_edge = |x: i32, y: i32| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+
+        let mut walls: Vec<Vec<bool>> = (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| is_edge(x, y) || rng.gen::<f32>() < CAVE_WALL_CHANCE)
+                    .collect()
+            })
+            .collect();
+
+        for _ in 0..CAVE_SMOOTHING_PASSES {
+            walls = (0..width)
+                .map(|x| {
+                    (0..height)
+                        .map(|y| {
+                            is_edge(x, y) || self.walls_around(&walls, x, y) >= CAVE_WALL_NEIGHBOURS
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+
+        for x in 0..width {
+            for y in 0..height {
+                if !walls[x as usize][y as usize] {
+                    map.carve(x, y);
+                }
+            }
+        }
+        let start = keep_largest_cave(map, rng);
+        open_layout(map, start)
+    }
+}
+
+impl CaveGenerator {
+    //Walls in the 3x3 square centered on (x, y). Anything off the map counts as wall
+    fn walls_around(&self, walls: &[Vec<bool>], x: i32, y: i32) -> usize {
+        let mut count = 0;
+        for nx in x - 1..=x + 1 {
+            for ny in y - 1..=y + 1 {
+                let wall = walls
+                    .get(nx as usize)
+                    .and_then(|column| column.get(ny as usize))
+                    .map_or(true, |&wall| wall);
+                if wall {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+///
+/// A walker stumbles around the map digging wherever it goes, until enough of it is open.
+/// Everything it digs is connected, as it only ever moves one step at a time.
+///
+pub struct DrunkardsWalkGenerator;
+
+impl MapGenerator for DrunkardsWalkGenerator {
+    fn generate<R: Rng>(&self, map: &mut Map, rng: &mut R) -> Layout {
+        let start = (map.width / 2, map.height / 2);
+        let interior = ((map.width - 2) * (map.height - 2)) as f32;
+        let wanted = (interior * DRUNKARD_FLOOR_FRACTION) as usize;
+
+        let mut dug = vec![start];
+        map.carve(start.0, start.1);
+        while dug.len() < wanted {
+            // each walk sets off from somewhere already dug, so the cave stays in one piece
+            let (mut x, mut y) = dug[rng.gen_range(0, dug.len())];
+            for _ in 0..DRUNKARD_STEPS {
+                let (dx, dy) = [(0, -1), (1, 0), (0, 1), (-1, 0)][rng.gen_range(0, 4)];
+                // the outer edge of the map always stays wall
+                if x + dx < 1 || x + dx > map.width - 2 || y + dy < 1 || y + dy > map.height - 2 {
+                    continue;
+                }
+                x += dx;
+                y += dy;
+                if map.get_tile(x, y).blocked {
+                    map.carve(x, y);
+                    dug.push((x, y));
+                }
+            }
+        }
+        open_layout(map, start)
+    }
+}
+
+//An L-shaped tunnel between two points, turning the corner one of the two ways at random
+fn tunnel_between<R: Rng>(map: &mut Map, from: (i32, i32), to: (i32, i32), rng: &mut R) {
+    let ((prev_x, prev_y), (new_x, new_y)) = (from, to);
+    // draw a coin (random bool value -- either true or false)
+    if rng.gen() {
+        // first move horizontally, then vertically
+        map.create_h_tunnel(prev_x, new_x, prev_y);
+        map.create_v_tunnel(prev_y, new_y, new_x);
+    } else {
+        // first move vertically, then horizontally
+        map.create_v_tunnel(prev_y, new_y, prev_x);
+        map.create_h_tunnel(prev_x, new_x, new_y);
+    }
+}
+
+//The player starts in the first room and the stairs are in the last. Every corridor gets a door
+//where it goes through a room's wall. A map too small to fit any room is dug out as a cave instead
+fn rooms_layout<R: Rng>(map: &mut Map, rooms: Vec<Rect>, rng: &mut R) -> Layout {
+    if rooms.is_empty() {
+        return DrunkardsWalkGenerator.generate(map, rng);
+    }
+
+    let mut doors: Vec<(i32, i32)> = vec![];
+    for room in rooms.iter() {
+        let walls_across = (room.x1..=room.x2).flat_map(|x| vec![(x, room.y1), (x, room.y2)]);
+        let walls_down = (room.y1..=room.y2).flat_map(|y| vec![(room.x1, y), (room.x2, y)]);
+        for (x, y) in walls_across.chain(walls_down) {
+            // never two doors in a row, where a corridor passes through two walls side by side
+            let next_to_door = doors
+                .iter()
+                .any(|&(door_x, door_y)| (door_x - x).abs() + (door_y - y).abs() <= 1);
+            if is_doorway(map, x, y) && !next_to_door {
+                doors.push((x, y));
+            }
+        }
+    }
+
+    Layout {
+        start: rooms[0].center(),
+        exit: rooms[rooms.len() - 1].center(),
+        areas: rooms[1..].to_vec(),
+        doors,
+    }
+}
+
+//A gap one tile wide, with floor on two opposite sides and wall on the other two
+fn is_doorway(map: &Map, x: i32, y: i32) -> bool {
+    if x < 1 || y < 1 || x > map.width - 2 || y > map.height - 2 {
+        return false;
+    }
+    let floor = |x: i32, y: i32| !map.get_tile(x, y).blocked;
+    let across = floor(x - 1, y) && floor(x + 1, y) && !floor(x, y - 1) && !floor(x, y + 1);
+    let down = floor(x, y - 1) && floor(x, y + 1) && !floor(x - 1, y) && !floor(x + 1, y);
+    floor(x, y) && (across || down)
+}
+
+//Fills in every floor tile that can't be reached from the biggest open space, and returns a
+//random tile of what's left
+fn keep_largest_cave<R: Rng>(map: &mut Map, rng: &mut R) -> (i32, i32) {
+    let mut caves = regions(map).into_iter();
+    let largest = caves.next().unwrap_or_default();
+    for (x, y) in caves.flatten() {
+        map.fill(x, y);
+    }
+    if largest.is_empty() {
+        // the smoothing closed everything up, so dig out a spot to stand on
+        let center = (map.width / 2, map.height / 2);
+        map.carve(center.0, center.1);
+        return center;
+    }
+    largest[rng.gen_range(0, largest.len())]
+}
+
+//For levels without rooms: the stairs go as far from the start as can be walked, and the
+//map is cut into squares to populate, skipping any without floor and the one the player is in
+fn open_layout(map: &Map, start: (i32, i32)) -> Layout {
+    let distances = map.dijkstra_map(&[start], |tile| walk_cost(tile, true));
+    let mut exit = start;
+    for x in 0..map.width {
+        for y in 0..map.height {
+            let further = distances.value(x, y) > distances.value(exit.0, exit.1);
+            if further {
+                exit = (x, y);
+            }
+        }
+    }
+
+    let mut areas = vec![];
+    for area_x in (1..map.width - 1).step_by(AREA_SIZE as usize) {
+        for area_y in (1..map.height - 1).step_by(AREA_SIZE as usize) {
+            let w = AREA_SIZE.min(map.width - 1 - area_x);
+            let h = AREA_SIZE.min(map.height - 1 - area_y);
+            // rooms are populated inside their walls, so the rectangle goes one tile further out
+            let area = Rect::new(area_x - 1, area_y - 1, w + 1, h + 1);
+            let holds_start =
+                start.0 > area.x1 && start.0 < area.x2 && start.1 > area.y1 && start.1 < area.y2;
+            let has_floor = (area_x..area_x + w)
+                .any(|x| (area_y..area_y + h).any(|y| !map.get_tile(x, y).blocked));
+            if has_floor && !holds_start {
+                areas.push(area);
+            }
+        }
+    }
+
+    Layout {
+        start,
+        exit,
+        areas,
+        doors: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Isaac64Rng, SeedableRng};
+
+    const GENERATORS: [Generator; 4] = [
+        Generator::RoomsAndCorridors,
+        Generator::Bsp,
+        Generator::Caves,
+        Generator::DrunkardsWalk,
+    ];
+
+    fn floor(map: &Map, (x, y): (i32, i32)) -> bool {
+        map.valid_point(x, y) && !map.get_tile(x, y).blocked
+    }
+
+    #[test]
+    fn start_exit_and_doors_are_on_the_floor() {
+        for &generator in GENERATORS.iter() {
+            for seed in 0..10 {
+                let mut map = Map::new(80, 43);
+                let layout = generator.generate(&mut map, &mut Isaac64Rng::from_seed(&[seed]));
+                assert!(floor(&map, layout.start), "{} seed {}", generator, seed);
+                assert!(floor(&map, layout.exit), "{} seed {}", generator, seed);
+                for &(x, y) in layout.doors.iter() {
+                    assert!(floor(&map, (x, y)), "{} seed {}", generator, seed);
+                    // a corridor through two walls side by side only gets the one door
+                    let side_by_side = layout
+                        .doors
+                        .iter()
+                        .any(|&(other_x, other_y)| (other_x - x).abs() + (other_y - y).abs() == 1);
+                    assert!(!side_by_side, "{} seed {}", generator, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_outer_edge_stays_wall() {
+        for &generator in GENERATORS.iter() {
+            let mut map = Map::new(80, 43);
+            generator.generate(&mut map, &mut Isaac64Rng::from_seed(&[1]));
+            for x in 0..map.width {
+                assert!(!floor(&map, (x, 0)) && !floor(&map, (x, map.height - 1)));
+            }
+            for y in 0..map.height {
+                assert!(!floor(&map, (0, y)) && !floor(&map, (map.width - 1, y)));
+            }
+        }
+    }
+
+    #[test]
+    fn maps_too_small_for_rooms_still_get_a_level() {
+        // long, thin maps can be split along their length into pieces too narrow for rooms
+        for &(width, height) in [(8, 6), (80, 6), (6, 80), (25, 6)].iter() {
+            for &generator in GENERATORS.iter() {
+                let mut map = Map::new(width, height);
+                let layout = generator.generate(&mut map, &mut Isaac64Rng::from_seed(&[1]));
+                assert!(
+                    floor(&map, layout.start),
+                    "{} on a {}x{} map",
+                    generator,
+                    width,
+                    height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generators_are_named_on_the_command_line() {
+        for &generator in GENERATORS.iter() {
+            assert_eq!(generator.to_string().parse(), Ok(generator));
+        }
+        assert_eq!(
+            "cave".parse::<Generator>(),
+            Err("Unknown map generator: cave".to_string())
+        );
+    }
+}
@@ -1,27 +1,34 @@
 extern crate rand;
 
+use entities::equipment::{Equipment, Slot};
+use entities::item::Item;
 use entities::object::Ai;
 use entities::object::DeathCallback;
 use entities::object::Fighter;
 use entities::object::Object;
 
-use crate::entities::light::LightSource;
+use crate::entities::light::{Animation, Illumination, LightSource, TORCH_COLOR};
 
 use self::rand::Rng;
+use colors;
+use map::connectivity::{connect_regions, unreachable_objects, unreachable_regions};
+use map::dijkstra::DijkstraMap;
+use map::fov::FovAlgorithm;
+use map::fov_map::FovMap;
+use map::generator::Generator;
+use map::movement_helper::is_blocked;
 use map::rect::Rect;
-use map::shadow_line::Shadow;
-use map::shadow_line::ShadowLine;
 use map::tile::Tile;
 use std::cmp;
 use std::vec;
-use tcod::colors;
 
-const ROOM_MAX_SIZE: i32 = 10;
-const ROOM_MIN_SIZE: i32 = 6;
-const MAX_ROOMS: i32 = 30;
-const DEFAULT_SHADE_FACTOR: f32 = 0.8;
-const MAX_ROOM_MONSTERS: i32 = 3;
+//How much of a tile's color shows when no light reaches it
+pub const AMBIENT_LIGHT: f32 = 0.2;
+const MAX_ROOM_ITEMS: i32 = 2;
+const TORCH_FUEL: i32 = 500;
+const LANTERN_FUEL: i32 = 1000;
 
+#[derive(Serialize, Deserialize)]
 pub struct Map {
     pub map: Vec<Vec<Tile>>,
     pub width: i32,
@@ -32,7 +39,7 @@ impl Map {
     pub fn new(width: i32, height: i32) -> Self {
         Map {
             map: vec![
-                vec![Tile::wall(0, 0, DEFAULT_SHADE_FACTOR); height as usize];
+                vec![Tile::wall(0, 0, Illumination::grey(AMBIENT_LIGHT)); height as usize];
                 width as usize
             ],
             width: width,
@@ -40,11 +47,6 @@ impl Map {
         }
     }
 
-    pub fn is_in_fov(&self, object: &Object) -> bool {
-        let (x, y) = object.get_pos();
-        return self.get_tile(x, y).visible;
-    }
-
     pub fn get_tile(&self, x: i32, y: i32) -> Tile {
         return self.map[x as usize][y as usize];
     }
@@ -60,221 +62,237 @@ impl Map {
         return true;
     }
 
-    pub fn make_rand_map(&mut self) -> Vec<Object> {
-        let mut rooms = vec![];
-        let mut objects = vec![];
-        for _ in 0..MAX_ROOMS {
-            let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-            let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-            // random position without going out of the boundaries of the map
-            let x = rand::thread_rng().gen_range(0, self.width - w);
-            let y = rand::thread_rng().gen_range(0, self.height - h);
-
-            let new_room = Rect::new(x, y, w, h);
-            let failed = rooms
-                .iter()
-                .any(|other_room| new_room.intersects_with(other_room));
-
-            if !failed {
-                self.create_room(new_room);
-                let (new_x, new_y) = new_room.center();
-
-                if rooms.is_empty() {
-                    //start = (new_x, new_y);
-                    let mut player = Object::new(new_x, new_y, '@', "player", colors::WHITE, true);
-                    player.alive = true;
-                    player.fighter = Some(Fighter {
-                        max_hp: 30,
-                        hp: 30,
-                        defense: 2,
-                        power: 5,
-                        on_death: DeathCallback::Player,
-                    });
-                    player.light = Some(LightSource::new(3.0, 8.0));
-                    objects.push(player);
-                } else {
-                    // all rooms after the first:
-                    // connect it to the previous room with a tunnel
-
-                    // center coordinates of the previous room
-                    let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
-
-                    // draw a coin (random bool value -- either true or false)
-                    if rand::random() {
-                        // first move horizontally, then vertically
-                        self.create_h_tunnel(prev_x, new_x, prev_y);
-                        self.create_v_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        // first move vertically, then horizontally
-                        self.create_v_tunnel(prev_y, new_y, prev_x);
-                        self.create_h_tunnel(prev_x, new_x, new_y);
-                    }
-                    //Now add monsters to the room
-                    self.place_object(new_room, &mut objects)
-                }
+    ///
+    /// Distance from every tile to the nearest goal, see DijkstraMap
+    ///
+    pub fn dijkstra_map<F>(&self, goals: &[(i32, i32)], cost: F) -> DijkstraMap
+    where
+        F: Fn(&Tile) -> Option<i32>,
+    {
+        DijkstraMap::new(self, goals, cost)
+    }
+
+    //All randomness comes from rng, so the same seed always produces the same map and objects.
+    //The player is placed where the generator says to start, and is always the first object returned.
+    //Every floor tile and object can be walked to from the start
+    pub fn make_rand_map<R: Rng>(
+        &mut self,
+        player: Object,
+        level: u32,
+        generator: Generator,
+        rng: &mut R,
+    ) -> Vec<Object> {
+        let mut player = player;
+        loop {
+            let mut objects = vec![player];
+            let layout = generator.generate(self, rng);
+            for &(x, y) in layout.doors.iter() {
+                self.map[x as usize][y as usize] =
+                    Tile::door(x, y, Illumination::grey(AMBIENT_LIGHT));
+            }
+            connect_regions(self, layout.start);
+            objects[0].set_pos(layout.start.0, layout.start.1);
+
+            //Now add monsters and items
+            for area in layout.areas {
+                self.place_object(area, &mut objects, level, rng)
+            }
 
-                // finally, append the new room to the list
-                rooms.push(new_room);
+            // create stairs where the generator put the exit
+            let (exit_x, exit_y) = layout.exit;
+            let mut stairs = Object::new(exit_x, exit_y, '>', "stairs", colors::WHITE, false);
+            stairs.always_visible = true;
+            stairs.stairs = true;
+            objects.push(stairs);
+
+            if unreachable_regions(self, layout.start).is_empty()
+                && unreachable_objects(self, layout.start, &objects).is_empty()
+            {
+                return objects;
             }
+            // something is still stranded, so start over on a fresh map
+            player = objects.swap_remove(0);
+            *self = Map::new(self.width, self.height);
         }
-        objects
     }
 
-    fn create_room(&mut self, room: Rect) {
+    pub fn carve(&mut self, x: i32, y: i32) {
+        self.map[x as usize][y as usize] = Tile::floor(x, y, Illumination::grey(AMBIENT_LIGHT));
+    }
+
+    pub fn fill(&mut self, x: i32, y: i32) {
+        self.map[x as usize][y as usize] = Tile::wall(x, y, Illumination::grey(AMBIENT_LIGHT));
+    }
+
+    pub fn create_room(&mut self, room: Rect) {
         for x in (room.x1 + 1)..room.x2 {
             for y in (room.y1 + 1)..room.y2 {
-                self.map[x as usize][y as usize] = Tile::floor(x, y, DEFAULT_SHADE_FACTOR);
+                self.carve(x, y);
             }
         }
     }
 
-    fn create_h_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
+    pub fn create_h_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
         for x in cmp::min(x1, x2)..cmp::max(x1, x2) + 1 {
-            self.map[x as usize][y as usize] = Tile::floor(x, y, DEFAULT_SHADE_FACTOR);
+            self.carve(x, y);
         }
     }
 
-    fn create_v_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
+    pub fn create_v_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
         for y in cmp::min(y1, y2)..cmp::max(y1, y2) + 1 {
-            self.map[x as usize][y as usize] = Tile::floor(x, y, DEFAULT_SHADE_FACTOR);
+            self.carve(x, y);
         }
     }
 
     //Visibility
-    pub fn refresh_visibility(&mut self, x: i32, y: i32) {
-        println!();
-        println!();
-        println!("new refresh");
-        for octant in 0..8 {
-            self.refresh_octant(x, y, octant);
-        }
+    ///
+    /// Works out what can be seen from (x, y), out to radius tiles, into fov.
+    /// The map itself is left untouched, so any number of viewers can share it.
+    ///
+    pub fn compute_fov(
+        &self,
+        x: i32,
+        y: i32,
+        radius: i32,
+        algorithm: FovAlgorithm,
+        fov: &mut FovMap,
+    ) {
+        fov.clear();
+        //The viewer can always see the tile it's standing on
+        fov.set_visible(x, y);
+        algorithm.implementation().compute(self, x, y, radius, fov);
     }
 
-    //Each octant represents 45 degrees
-    fn refresh_octant(&mut self, x: i32, y: i32, octant: i32) {
-        let mut line = ShadowLine::new();
-        let mut full_shadow = false;
-        let mut row = 1;
+    pub fn field_of_view(&self, x: i32, y: i32, radius: i32, algorithm: FovAlgorithm) -> FovMap {
+        let mut fov = FovMap::new(self.width, self.height);
+        self.compute_fov(x, y, radius, algorithm, &mut fov);
+        fov
+    }
 
-        loop {
-            let (x_transform, y_transform) = self.transform_octant(row, 0, octant);
-            let (pos_x, pos_y) = (x + x_transform, y + y_transform);
+    /// true for anything outside the map
+    pub fn blocks_sight(&self, x: i32, y: i32) -> bool {
+        !self.valid_point(x, y) || self.map[x as usize][y as usize].block_sight
+    }
 
-            if !self.valid_point(pos_x, pos_y) {
-                break;
+    //Darkens every tile again, so lights that moved or turned don't leave a lit trail behind
+    pub fn clear_lighting(&mut self) {
+        for column in self.map.iter_mut() {
+            for tile in column.iter_mut() {
+                tile.light = Illumination::grey(AMBIENT_LIGHT);
             }
-            for col in 0..row + 1 {
-                //if out of bounds, break
-                let (x_transform, y_transform) = self.transform_octant(row, col, octant);
-                let (pos_x, pos_y) = (x + x_transform, y + y_transform);
-
-                if !self.valid_point(pos_x, pos_y) {
-                    break;
-                }
+        }
+    }
 
-                if full_shadow {
-                    self.map[pos_x as usize][pos_y as usize].visible = false;
-                    self.map[pos_x as usize][pos_y as usize].shade_factor = DEFAULT_SHADE_FACTOR
-                } else {
-                    let projection = self.project_tile(row as f32, col as f32);
-                    let visible = !line.is_in_shadow(&projection);
-                    if visible && self.map[pos_x as usize][pos_y as usize].block_sight {
-                        line.add(projection);
-                        full_shadow = line.is_full_shadow();
-                    }
-                    if visible {
-                        self.map[pos_x as usize][pos_y as usize].visible = true;
-                    } else {
-                        self.map[pos_x as usize][pos_y as usize].shade_factor =
-                            DEFAULT_SHADE_FACTOR;
-                        self.map[pos_x as usize][pos_y as usize].visible = false;
-                    }
+    pub fn mark_explored(&mut self, fov: &FovMap) {
+        for (x, column) in self.map.iter_mut().enumerate() {
+            for (y, tile) in column.iter_mut().enumerate() {
+                if fov.is_visible(x as i32, y as i32) {
+                    tile.explored = true;
                 }
             }
-            row = row + 1;
         }
-        //for row in 1..
     }
 
     ///
-    /// Lights all tiles around the point, using the light source
-    ///
+    /// Lights all tiles around the point that the light has a line of sight to.
+    /// Directional lights only reach the tiles inside their cone, pointed along facing.
     ///
-    pub fn light_tiles(&mut self, x: i32, y: i32, light_source: &LightSource) {
-        let max_dist: i32 = light_source.max_dist as i32;
-
-        for width in -max_dist..=max_dist {
-            let max_height = (max_dist - width.abs());
-
-            for height in -max_height..=max_height {
-                let tile_x = x + width;
-                let tile_y = y + height;
-
-                if !self.valid_point(tile_x, tile_y) {
-                    continue;
-                }
-                if self.get_tile(tile_x, tile_y).visible {
-                    let new_shade_factor =
-                        self.get_shade_factor(width.abs(), height.abs(), light_source);
-                    if new_shade_factor < self.map[tile_x as usize][tile_y as usize].shade_factor {
-                        self.map[tile_x as usize][tile_y as usize].shade_factor = new_shade_factor;
-                    }
+    pub fn light_tiles(
+        &mut self,
+        x: i32,
+        y: i32,
+        facing: (i32, i32),
+        light_source: &LightSource,
+        algorithm: FovAlgorithm,
+    ) {
+        //Walls cast shadows for lights the same way they do for sight
+        let max_dist = light_source.max_dist as i32;
+        let reached = self.field_of_view(x, y, max_dist, algorithm);
+
+        for tile_x in x - max_dist..=x + max_dist {
+            for tile_y in y - max_dist..=y + max_dist {
+                let (dx, dy) = (tile_x - x, tile_y - y);
+                if reached.is_visible(tile_x, tile_y) && light_source.illuminates(facing, dx, dy) {
+                    let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                    self.map[tile_x as usize][tile_y as usize]
+                        .light
+                        .add(light_source.color, light_source.strength(distance));
                 }
             }
         }
     }
 
-    fn get_shade_factor(&self, x: i32, y: i32, light_source: &LightSource) -> f32 {
-        //println!("x: {0}, y: {1}", x, y);
-        let distance = ((x * x + y * y) as f64).sqrt() as f32;
-
-        //println!("distance: {0}", distance);
-        let intensity = light_source.calc_shade_percent(distance);
-
-        let ret = f32::min(DEFAULT_SHADE_FACTOR, (intensity) as f32);
-        //println!("intensity: {0}", intensity);
-        return ret;
-    }
-
-    fn project_tile(&self, row: f32, col: f32) -> Shadow {
-        let top_left = col / (row + 2.0);
-        let bottom_right = (col + 1.0) / (row + 1.0);
-        Shadow::new(top_left, bottom_right)
-    }
-
-    // takes in a point in 2d spaces, and transforms it to the relevant octant.
-    fn transform_octant(&self, row: i32, col: i32, octant: i32) -> (i32, i32) {
-        match octant {
-            0 => return (col, -row),
-            1 => return (row, -col),
-            2 => return (row, col),
-            3 => return (col, row),
-            4 => return (-col, row),
-            5 => return (-row, col),
-            6 => return (-row, -col),
-            _ => return (-col, -row),
-        }
-    }
-
     //Monsters
 
-    fn place_object(&mut self, room: Rect, objects: &mut Vec<Object>) {
-        let num_monsters = rand::thread_rng().gen_range(0, MAX_ROOM_MONSTERS + 1);
+    fn place_object<R: Rng>(
+        &mut self,
+        room: Rect,
+        objects: &mut Vec<Object>,
+        level: u32,
+        rng: &mut R,
+    ) {
+        // maximum number of monsters per room, and how often they're trolls, grow with depth
+        let max_monsters = from_dungeon_level(
+            &[
+                Transition { level: 1, value: 3 },
+                Transition { level: 4, value: 4 },
+                Transition { level: 6, value: 5 },
+            ],
+            level,
+        );
+        let troll_chance = from_dungeon_level(
+            &[
+                Transition {
+                    level: 1,
+                    value: 20,
+                },
+                Transition {
+                    level: 3,
+                    value: 30,
+                },
+                Transition {
+                    level: 5,
+                    value: 45,
+                },
+                Transition {
+                    level: 7,
+                    value: 60,
+                },
+            ],
+            level,
+        );
+
+        let num_monsters = rng.gen_range(0, max_monsters + 1);
 
         for _ in 0..num_monsters {
-            let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-            let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+            let x = rng.gen_range(room.x1 + 1, room.x2);
+            let y = rng.gen_range(room.y1 + 1, room.y2);
+            // areas of open levels aren't all floor, and nothing should share a tile
+            if is_blocked(x, y, self, objects) {
+                continue;
+            }
 
-            let mut monster = if rand::random::<f32>() < 0.8 {
+            let mut monster = if rng.gen_range(0, 100) >= troll_chance {
                 let mut orc = Object::new(x, y, 'o', "Orc", colors::DESATURATED_GREEN, true);
                 orc.fighter = Some(Fighter {
                     max_hp: 10,
                     hp: 10,
                     defense: 0,
                     power: 3,
+                    xp: 35,
                     on_death: DeathCallback::Monster,
                 });
-                orc.ai = Some(Ai::Basic);
+                orc.ai = Some(Ai::basic());
+                // orcs know how to work a door, trolls just bump into them
+                orc.opens_doors = true;
+                // half of the orcs carry a torch
+                if rng.gen::<f32>() < 0.5 {
+                    orc.light = Some(LightSource {
+                        color: TORCH_COLOR,
+                        animation: Animation::Flicker,
+                        ..LightSource::new(3.0, 8.0)
+                    });
+                }
                 orc
             } else {
                 let mut troll = Object::new(x, y, 'T', "Troll", colors::DARKER_GREEN, true);
@@ -283,17 +301,278 @@ impl Map {
                     hp: 16,
                     defense: 1,
                     power: 4,
+                    xp: 100,
                     on_death: DeathCallback::Monster,
                 });
-                troll.ai = Some(Ai::Basic);
+                troll.ai = Some(Ai::basic());
+                // trolls' eyes glow, lighting whatever they look at
+                troll.light = Some(LightSource {
+                    color: colors::RED,
+                    animation: Animation::Strobe { on: 12, off: 1 }, // blinking
+                    ..LightSource::cone(2.0, 6.0, 60)
+                });
                 troll
             };
             monster.alive = true;
+            objects.push(monster);
+        }
 
-            if rand::random::<f32>() < 0.5 {
-                monster.light = Some(LightSource::new(3.0, 8.0));
+        //Items
+        let num_items = rng.gen_range(0, MAX_ROOM_ITEMS + 1);
+
+        for _ in 0..num_items {
+            let x = rng.gen_range(room.x1 + 1, room.x2);
+            let y = rng.gen_range(room.y1 + 1, room.y2);
+
+            // only place it if the tile is not blocked
+            if !is_blocked(x, y, self, objects) {
+                let loot = random_loot(level, rng);
+                objects.push(make_loot(loot, x, y));
             }
-            objects.push(monster);
         }
     }
 }
+
+#[cfg(test)]
+impl Map {
+    //Builds an unlit map from rows of '#' for walls and '+' for closed doors. Anything else
+    //is floor, so tests can mark spots of interest on it
+    pub fn from_rows(rows: &[&str]) -> Self {
+        let mut map = Map::new(rows[0].len() as i32, rows.len() as i32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                let (x, y) = (x as i32, y as i32);
+                let light = Illumination::grey(0.0);
+                match tile {
+                    '#' => {}
+                    '+' => map.map[x as usize][y as usize] = Tile::door(x, y, light),
+                    _ => map.map[x as usize][y as usize] = Tile::floor(x, y, light),
+                }
+            }
+        }
+        map
+    }
+}
+
+//Everything that can be found lying around in a room
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Loot {
+    HealingPotion,
+    LightningScroll,
+    FireballScroll,
+    ConfusionScroll,
+    Sword,
+    Shield,
+    Armor,
+    Torch,
+    Lantern,
+    BullseyeLantern,
+    OilFlask,
+    GlowingSword,
+}
+
+//Picks an item to place, with equipment only showing up further down
+fn random_loot<R: Rng>(level: u32, rng: &mut R) -> Loot {
+    let chances = [
+        (Loot::HealingPotion, 70),
+        (Loot::LightningScroll, 10),
+        (Loot::FireballScroll, 10),
+        (Loot::ConfusionScroll, 10),
+        (Loot::Torch, 10),
+        (
+            Loot::OilFlask,
+            from_dungeon_level(&[Transition { level: 2, value: 5 }], level),
+        ),
+        (
+            Loot::Lantern,
+            from_dungeon_level(&[Transition { level: 3, value: 5 }], level),
+        ),
+        (
+            Loot::BullseyeLantern,
+            from_dungeon_level(&[Transition { level: 4, value: 5 }], level),
+        ),
+        (
+            Loot::GlowingSword,
+            from_dungeon_level(&[Transition { level: 5, value: 5 }], level),
+        ),
+        (
+            Loot::Shield,
+            from_dungeon_level(
+                &[Transition {
+                    level: 2,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Loot::Sword,
+            from_dungeon_level(
+                &[Transition {
+                    level: 3,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Loot::Armor,
+            from_dungeon_level(
+                &[Transition {
+                    level: 5,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+    ];
+    let total: u32 = chances.iter().map(|&(_, chance)| chance).sum();
+    let mut dice = rng.gen_range(0, total);
+    for &(loot, chance) in chances.iter() {
+        if dice < chance {
+            return loot;
+        }
+        dice -= chance;
+    }
+    Loot::HealingPotion
+}
+
+fn make_loot(loot: Loot, x: i32, y: i32) -> Object {
+    match loot {
+        Loot::HealingPotion => {
+            let mut object = Object::new(x, y, '!', "healing potion", colors::VIOLET, false);
+            object.item = Some(Item::Heal);
+            object
+        }
+        Loot::LightningScroll => {
+            let mut object = Object::new(
+                x,
+                y,
+                '#',
+                "scroll of lightning bolt",
+                colors::LIGHT_YELLOW,
+                false,
+            );
+            object.item = Some(Item::Lightning);
+            object
+        }
+        Loot::FireballScroll => {
+            let mut object =
+                Object::new(x, y, '#', "scroll of fireball", colors::LIGHT_YELLOW, false);
+            object.item = Some(Item::Fireball);
+            object
+        }
+        Loot::ConfusionScroll => {
+            let mut object = Object::new(
+                x,
+                y,
+                '#',
+                "scroll of confusion",
+                colors::LIGHT_YELLOW,
+                false,
+            );
+            object.item = Some(Item::Confuse);
+            object
+        }
+        Loot::Sword => {
+            let mut object = Object::new(x, y, '/', "sword", colors::SKY, false);
+            object.equipment = Some(Equipment {
+                power_bonus: 3,
+                ..Equipment::new(Slot::Weapon)
+            });
+            object.item = Some(Item::Equipment);
+            object
+        }
+        Loot::Shield => {
+            let mut object = Object::new(x, y, '[', "shield", colors::DARKER_ORANGE, false);
+            object.equipment = Some(Equipment {
+                defense_bonus: 1,
+                ..Equipment::new(Slot::Shield)
+            });
+            object.item = Some(Item::Equipment);
+            object
+        }
+        Loot::Armor => {
+            let mut object = Object::new(x, y, ']', "leather armor", colors::DARKER_ORANGE, false);
+            object.equipment = Some(Equipment {
+                defense_bonus: 1,
+                max_hp_bonus: 10,
+                ..Equipment::new(Slot::Armor)
+            });
+            object.item = Some(Item::Equipment);
+            object
+        }
+        Loot::Torch => make_torch(x, y),
+        Loot::Lantern => {
+            let mut object = Object::new(x, y, '*', "lantern", colors::GOLD, false);
+            object.equipment = Some(Equipment {
+                light: Some(LightSource::new(5.0, 10.0)),
+                fuel: Some(LANTERN_FUEL),
+                ..Equipment::new(Slot::Light)
+            });
+            object.item = Some(Item::Equipment);
+            object
+        }
+        Loot::BullseyeLantern => {
+            // throws its light a long way, but only straight ahead
+            let mut object = Object::new(x, y, '*', "bullseye lantern", colors::GOLD, false);
+            object.equipment = Some(Equipment {
+                light: Some(LightSource::cone(8.0, 10.0, 90)),
+                fuel: Some(LANTERN_FUEL),
+                ..Equipment::new(Slot::Light)
+            });
+            object.item = Some(Item::Equipment);
+            object
+        }
+        Loot::OilFlask => {
+            let mut object = Object::new(x, y, '!', "flask of oil", colors::AMBER, false);
+            object.item = Some(Item::Refuel);
+            object
+        }
+        Loot::GlowingSword => {
+            let mut object = Object::new(x, y, '/', "glowing sword", colors::LIGHT_CYAN, false);
+            object.equipment = Some(Equipment {
+                power_bonus: 2,
+                light: Some(LightSource {
+                    color: colors::LIGHT_BLUE,
+                    animation: Animation::Pulse { period: 10 },
+                    ..LightSource::new(1.0, 4.0)
+                }),
+                ..Equipment::new(Slot::Weapon)
+            });
+            object.item = Some(Item::Equipment);
+            object
+        }
+    }
+}
+
+pub fn make_torch(x: i32, y: i32) -> Object {
+    let mut object = Object::new(x, y, '*', "torch", colors::FLAME, false);
+    object.equipment = Some(Equipment {
+        light: Some(LightSource {
+            color: TORCH_COLOR,
+            animation: Animation::Flicker,
+            ..LightSource::new(3.0, 8.0)
+        }),
+        fuel: Some(TORCH_FUEL),
+        ..Equipment::new(Slot::Light)
+    });
+    object.item = Some(Item::Equipment);
+    object
+}
+
+//A value that applies from a given dungeon level onwards
+struct Transition {
+    level: u32,
+    value: u32,
+}
+
+/// Returns a value that depends on level. the table specifies what
+/// value occurs after each level, default is 0.
+fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+    table
+        .iter()
+        .rev()
+        .find(|transition| level >= transition.level)
+        .map_or(0, |transition| transition.value)
+}
@@ -1,7 +1,25 @@
 extern crate rand;
 
+pub mod connectivity;
+pub mod dijkstra;
+pub mod fov;
+pub mod fov_map;
+pub mod generator;
 pub mod map;
 pub mod movement_helper;
+pub mod pathfinding;
 pub mod rect;
 pub mod shadow_line;
 pub mod tile;
+
+//The eight steps to the tiles around a tile, straight ones first
+pub const NEIGHBOURS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (1, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+];
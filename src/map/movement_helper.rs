@@ -1,15 +1,29 @@
 use entities::object::Object;
 use map::map::Map;
+use map::pathfinding::find_path;
 
-pub fn move_by(idx: usize, dx: i32, dy: i32, map: &Map, objects: &mut [Object]) {
+//Returns whether the object got anywhere: stepped, or opened the door in its way
+pub fn move_by(idx: usize, dx: i32, dy: i32, map: &mut Map, objects: &mut [Object]) -> bool {
     let (x, y) = objects[idx].get_pos();
 
     let new_x = x + dx;
     let new_y = y + dy;
 
-    if !is_blocked(new_x, new_y, map, objects) {
-        objects[idx].set_pos(new_x, new_y);
+    objects[idx].face(dx, dy);
+    if !map.valid_point(new_x, new_y) {
+        return false;
     }
+    // bumping into a closed door opens it, which takes the whole move
+    let door = &mut map.map[new_x as usize][new_y as usize];
+    if door.is_closed_door() && objects[idx].opens_doors {
+        door.open_door();
+        return true;
+    }
+    if is_blocked(new_x, new_y, map, objects) {
+        return false;
+    }
+    objects[idx].set_pos(new_x, new_y);
+    true
 }
 
 pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
@@ -22,7 +36,13 @@ pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
         .any(|object| object.blocks && object.get_pos() == (x, y))
 }
 
-pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects: &mut [Object]) {
+pub fn move_towards(
+    id: usize,
+    target_x: i32,
+    target_y: i32,
+    map: &mut Map,
+    objects: &mut [Object],
+) {
     // vector from this object to the target, and distance
     let (x, y) = objects[id].get_pos();
     let dx = target_x - x;
@@ -36,3 +56,36 @@ pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects:
     let dy = (dy as f32 / distance).round() as i32;
     move_by(id, dx, dy, map, objects);
 }
+
+///
+/// Takes one step along the shortest path to the target, walking around walls and other objects.
+/// Falls back to a straight-line step when there is no path at all.
+///
+pub fn move_astar(id: usize, target_x: i32, target_y: i32, map: &mut Map, objects: &mut [Object]) {
+    let start = objects[id].get_pos();
+    let opens_doors = objects[id].opens_doors;
+    match find_path(map, objects, start, (target_x, target_y), opens_doors)
+        .and_then(|path| path.first().cloned())
+    {
+        Some((next_x, next_y)) => {
+            move_by(id, next_x - start.0, next_y - start.1, map, objects);
+        }
+        None => move_towards(id, target_x, target_y, map, objects),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colors;
+
+    #[test]
+    fn moving_off_the_map_goes_nowhere() {
+        let mut map = Map::new(3, 3);
+        map.carve(0, 0);
+        let mut objects = vec![Object::new(0, 0, '@', "player", colors::WHITE, true)];
+        assert!(!move_by(0, -1, 0, &mut map, &mut objects));
+        assert!(!move_by(0, 0, -1, &mut map, &mut objects));
+        assert_eq!(objects[0].get_pos(), (0, 0));
+    }
+}
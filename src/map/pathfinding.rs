@@ -0,0 +1,146 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use entities::object::Object;
+use map::dijkstra::walk_cost;
+use map::map::Map;
+use map::NEIGHBOURS;
+
+//Costs are scaled by 10 so diagonals can be weighted without floats
+const STRAIGHT_COST: i32 = 10;
+const DIAGONAL_COST: i32 = 14;
+//Walking through a tile with a blocking object is possible, but only worth it for a long detour
+const BLOCKING_OBJECT_COST: i32 = 100;
+
+///
+/// A* search over the map tiles from start to goal, moving in 8 directions.
+/// Blocked tiles are impassable, tiles with blocking objects are just expensive.
+/// Closed doors are walked through if opens_doors, as the mover will open them on the way,
+/// and are as good as walls otherwise.
+/// Returns the steps to take (excluding start, including goal), or None if no path exists.
+///
+pub fn find_path(
+    map: &Map,
+    objects: &[Object],
+    start: (i32, i32),
+    goal: (i32, i32),
+    opens_doors: bool,
+) -> Option<Vec<(i32, i32)>> {
+    if !map.valid_point(goal.0, goal.1)
+        || walk_cost(&map.get_tile(goal.0, goal.1), opens_doors).is_none()
+    {
+        return None;
+    }
+
+    let index = |(x, y): (i32, i32)| (x + y * map.width) as usize;
+    let size = (map.width * map.height) as usize;
+
+    //Objects are counted once up front, rather than searched for on every tile
+    let mut occupied = vec![false; size];
+    for object in objects.iter().filter(|o| o.blocks) {
+        let (x, y) = object.get_pos();
+        if map.valid_point(x, y) {
+            occupied[index((x, y))] = true;
+        }
+    }
+
+    let mut cost_so_far = vec![i32::max_value(); size];
+    let mut came_from: Vec<Option<(i32, i32)>> = vec![None; size];
+    let mut frontier = BinaryHeap::new();
+
+    cost_so_far[index(start)] = 0;
+    frontier.push(Reverse((heuristic(start, goal), start)));
+
+    while let Some(Reverse((_, current))) = frontier.pop() {
+        if current == goal {
+            return Some(build_path(&came_from, start, goal, index));
+        }
+
+        for &(dx, dy) in NEIGHBOURS.iter() {
+            let next = (current.0 + dx, current.1 + dy);
+            if !map.valid_point(next.0, next.1)
+                || walk_cost(&map.get_tile(next.0, next.1), opens_doors).is_none()
+            {
+                continue;
+            }
+
+            let mut step_cost = if dx != 0 && dy != 0 {
+                DIAGONAL_COST
+            } else {
+                STRAIGHT_COST
+            };
+            //The goal is usually occupied by whatever is being chased
+            if next != goal && occupied[index(next)] {
+                step_cost += BLOCKING_OBJECT_COST;
+            }
+
+            let new_cost = cost_so_far[index(current)] + step_cost;
+            if new_cost < cost_so_far[index(next)] {
+                cost_so_far[index(next)] = new_cost;
+                came_from[index(next)] = Some(current);
+                frontier.push(Reverse((new_cost + heuristic(next, goal), next)));
+            }
+        }
+    }
+    None
+}
+
+//Octile distance, never overestimates with the costs above
+fn heuristic(from: (i32, i32), to: (i32, i32)) -> i32 {
+    let dx = (from.0 - to.0).abs();
+    let dy = (from.1 - to.1).abs();
+    let diagonal = dx.min(dy);
+    let straight = dx.max(dy) - diagonal;
+    diagonal * DIAGONAL_COST + straight * STRAIGHT_COST
+}
+
+fn build_path<F>(
+    came_from: &[Option<(i32, i32)>],
+    start: (i32, i32),
+    goal: (i32, i32),
+    index: F,
+) -> Vec<(i32, i32)>
+where
+    F: Fn((i32, i32)) -> usize,
+{
+    let mut path = vec![];
+    let mut current = goal;
+    while current != start {
+        path.push(current);
+        current = match came_from[index(current)] {
+            Some(prev) => prev,
+            None => break,
+        };
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_door_openers_path_through_closed_doors() {
+        let map = Map::from_rows(&[
+            "#########",
+            "#...+...#",
+            "#.#####.#",
+            "#.......#",
+            "#########",
+        ]);
+        let through = find_path(&map, &[], (1, 1), (7, 1), true).unwrap();
+        assert!(through.contains(&(4, 1)));
+        let around = find_path(&map, &[], (1, 1), (7, 1), false).unwrap();
+        assert!(!around.contains(&(4, 1)));
+        assert!(around.contains(&(4, 3)));
+    }
+
+    #[test]
+    fn closed_doors_shut_out_anything_that_cannot_open_them() {
+        let map = Map::from_rows(&["#######", "#..+..#", "#######"]);
+        assert!(find_path(&map, &[], (1, 1), (5, 1), true).is_some());
+        assert_eq!(find_path(&map, &[], (1, 1), (5, 1), false), None);
+        assert_eq!(find_path(&map, &[], (1, 1), (3, 1), false), None);
+    }
+}
@@ -21,6 +21,41 @@ impl Rect {
             (self.x1 <= other.x2)
         &&  (self.x2 >= other.x1)
         &&  (self.y1 <= other.y2)
-        &&  (self.y2 >= other.y2)
+        &&  (self.y2 >= other.y1)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_rooms_intersect() {
+        let room = Rect::new(5, 5, 6, 6);
+        assert!(room.intersects_with(&Rect::new(8, 8, 6, 6)));
+        assert!(room.intersects_with(&Rect::new(2, 2, 6, 6)));
+        assert!(room.intersects_with(&Rect::new(6, 6, 2, 2)));
+    }
+
+    #[test]
+    fn rooms_sharing_a_wall_intersect() {
+        let room = Rect::new(5, 5, 6, 6);
+        assert!(room.intersects_with(&Rect::new(11, 5, 6, 6)));
+        assert!(room.intersects_with(&Rect::new(5, 11, 6, 6)));
+    }
+
+    #[test]
+    fn rooms_apart_do_not_intersect() {
+        let room = Rect::new(5, 5, 6, 6);
+        assert!(!room.intersects_with(&Rect::new(20, 5, 6, 6)));
+        assert!(!room.intersects_with(&Rect::new(5, 20, 6, 6)));
+        assert!(!room.intersects_with(&Rect::new(5, 0, 4, 3)));
+    }
+
+    #[test]
+    fn rooms_reaching_past_the_bottom_intersect() {
+        // only rooms ending inside this one used to count
+        let room = Rect::new(5, 5, 6, 6);
+        assert!(room.intersects_with(&Rect::new(5, 8, 6, 6)));
+    }
+}
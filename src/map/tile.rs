@@ -1,4 +1,6 @@
-use tcod::colors::Color;
+use colors::Color;
+
+use entities::light::Illumination;
 
 const COLOR_DARK_WALL: Color = Color { r: 51, g: 21, b: 0 };
 const COLOR_DARK_FLOOR: Color = Color {
@@ -7,42 +9,88 @@ const COLOR_DARK_FLOOR: Color = Color {
     b: 20,
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Tile {
     pub blocked: bool,
     pub block_sight: bool,
     pub explored: bool,
-    pub visible: bool, //TODO: Eventually this should be a continous thing, not a bool
     pub x: i32,
     pub y: i32,
-    pub shade_factor: f32,
+    pub light: Illumination,
     pub color: Color,
+    pub door: bool, //Blocks movement and sight only while closed
 }
 
 impl Tile {
-    pub fn floor(x: i32, y: i32, shade_factor: f32) -> Self {
+    pub fn floor(x: i32, y: i32, light: Illumination) -> Self {
         Tile {
             blocked: false,
             block_sight: false,
             explored: false,
-            visible: false,
             x,
             y,
-            shade_factor,
+            light,
             color: COLOR_DARK_FLOOR,
+            door: false,
         }
     }
 
-    pub fn wall(x: i32, y: i32, shade_factor: f32) -> Self {
+    pub fn wall(x: i32, y: i32, light: Illumination) -> Self {
         Tile {
             blocked: true,
             block_sight: true,
             explored: false,
-            visible: false,
             x,
             y,
-            shade_factor,
+            light,
             color: COLOR_DARK_WALL,
+            door: false,
+        }
+    }
+
+    //Starts out closed
+    pub fn door(x: i32, y: i32, light: Illumination) -> Self {
+        Tile {
+            blocked: true,
+            block_sight: true,
+            door: true,
+            ..Tile::floor(x, y, light)
+        }
+    }
+
+    pub fn is_closed_door(&self) -> bool {
+        self.door && self.blocked
+    }
+
+    pub fn is_open_door(&self) -> bool {
+        self.door && !self.blocked
+    }
+
+    pub fn open_door(&mut self) {
+        if self.door {
+            self.blocked = false;
+            self.block_sight = false;
+        }
+    }
+
+    pub fn close_door(&mut self) {
+        if self.door {
+            self.blocked = true;
+            self.block_sight = true;
+        }
+    }
+
+    //Whether something could get onto this tile, counting closed doors as they can be opened
+    pub fn passable(&self) -> bool {
+        !self.blocked || self.door
+    }
+
+    //How a door is drawn over the floor, None for anything else
+    pub fn door_char(&self) -> Option<char> {
+        match (self.door, self.blocked) {
+            (true, true) => Some('+'),
+            (true, false) => Some('\''),
+            _ => None,
         }
     }
 }
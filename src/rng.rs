@@ -0,0 +1,72 @@
+use rand::{Isaac64Rng, Rng, SeedableRng};
+
+//Levels are generated from [seed, level], and levels start at 1, so this stream is never reused
+const TURN_STREAM: u64 = 0;
+
+///
+/// The random numbers drawn while turns are played out, seeded from the run's seed so a
+/// run can be replayed. Isaac64Rng can't be saved as it is, so a save keeps how many numbers
+/// have been drawn instead, and the generator is wound forward that far again once loaded.
+///
+#[derive(Serialize, Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    draws: u64,
+    #[serde(skip)]
+    rng: Option<Isaac64Rng>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            draws: 0,
+            rng: None,
+        }
+    }
+
+    fn rng(&mut self) -> &mut Isaac64Rng {
+        if self.rng.is_none() {
+            let mut rng = Isaac64Rng::from_seed(&[self.seed, TURN_STREAM]);
+            for _ in 0..self.draws {
+                rng.next_u64();
+            }
+            self.rng = Some(rng);
+        }
+        self.rng.as_mut().unwrap()
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        let value = self.rng().next_u32();
+        self.draws += 1;
+        value
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = self.rng().next_u64();
+        self.draws += 1;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn a_loaded_rng_carries_on_where_it_left_off() {
+        let mut rng = GameRng::new(5);
+        for _ in 0..10 {
+            rng.gen_range(-1, 2);
+        }
+        let mut loaded: GameRng =
+            serde_json::from_str(&serde_json::to_string(&rng).unwrap()).unwrap();
+
+        let expected: Vec<i32> = (0..20).map(|_| rng.gen_range(0, 100)).collect();
+        let drawn: Vec<i32> = (0..20).map(|_| loaded.gen_range(0, 100)).collect();
+        assert_eq!(drawn, expected);
+    }
+}
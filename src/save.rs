@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Write};
+
+use serde_json;
+
+use game::GameState;
+
+//Bump whenever a change to GameState (or anything it owns) would make older saves unreadable
+pub const SAVE_VERSION: u32 = 16;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Corrupt(serde_json::Error),
+    IncompatibleVersion { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "Could not access the save file: {}", e),
+            SaveError::Corrupt(e) => write!(f, "The save file is corrupt: {}", e),
+            SaveError::IncompatibleVersion { found, expected } => write!(
+                f,
+                "The save file is from an incompatible version ({}, expected {})",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Corrupt(e)
+    }
+}
+
+//Only the version is read first, so an old save is reported as such rather than as corrupt
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u32,
+    game: &'a GameState,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+    game: GameState,
+}
+
+pub fn save_game(game: &GameState, path: &str) -> Result<(), SaveError> {
+    let save_data = serde_json::to_string(&SaveFileRef {
+        version: SAVE_VERSION,
+        game,
+    })?;
+    let mut file = File::create(path)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_game(path: &str) -> Result<GameState, SaveError> {
+    let mut json_save_state = String::new();
+    let mut file = File::open(path)?;
+    file.read_to_string(&mut json_save_state)?;
+
+    let header: SaveHeader = serde_json::from_str(&json_save_state)?;
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::IncompatibleVersion {
+            found: header.version,
+            expected: SAVE_VERSION,
+        });
+    }
+
+    let save: SaveFile = serde_json::from_str(&json_save_state)?;
+    Ok(save.game)
+}
+
+//Removes a save that must not be continued, such as one from a run that has ended
+pub fn delete_save(path: &str) -> Result<(), SaveError> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colors;
+    use std::env;
+    use std::process;
+
+    //A file in the temp directory, unique to the test using it
+    fn temp_path(name: &str) -> String {
+        let path = env::temp_dir().join(format!("rust_rogue_{}_{}", name, process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn write_file(path: &str, contents: &str) {
+        File::create(path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+    }
+
+    #[test]
+    fn a_saved_game_loads_back_the_same() {
+        let path = temp_path("round_trip");
+        let mut game = GameState::new(80, 43, 3, None);
+        game.messages.add("Something to remember", colors::RED);
+        save_game(&game, &path).unwrap();
+        let loaded = load_game(&path).unwrap();
+        delete_save(&path).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&game).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_corrupt_save_is_reported_as_such() {
+        let path = temp_path("corrupt");
+        write_file(&path, "{\"version\": 1, \"game\": ");
+        let result = load_game(&path);
+        delete_save(&path).unwrap();
+
+        match result {
+            Err(SaveError::Corrupt(_)) => {}
+            other => panic!("expected a corrupt save, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn a_save_from_another_version_is_reported_as_such() {
+        let path = temp_path("old_version");
+        write_file(
+            &path,
+            &format!("{{\"version\": {}, \"game\": {{}}}}", SAVE_VERSION - 1),
+        );
+        let result = load_game(&path);
+        delete_save(&path).unwrap();
+
+        match result {
+            Err(SaveError::IncompatibleVersion { found, expected }) => {
+                assert_eq!((found, expected), (SAVE_VERSION - 1, SAVE_VERSION))
+            }
+            other => panic!("expected an incompatible version, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn deleting_a_missing_save_is_fine() {
+        let path = temp_path("missing");
+        assert!(delete_save(&path).is_ok());
+        assert!(load_game(&path).is_err());
+    }
+}
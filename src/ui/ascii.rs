@@ -0,0 +1,163 @@
+use entities::object::Object;
+use map::fov_map::FovMap;
+use map::map::Map;
+
+///
+/// Draws the map as text: '#' for walls, '.' for floor, '+' and '\'' for closed and open doors,
+/// and each object's own char on top.
+/// Given a FovMap, only shows what the player would: tiles they've explored, and objects in
+/// view or remembered. Without one the whole level is shown, for checking what was generated.
+/// Tiles in view and tiles only remembered look the same here, render_light tells them apart.
+///
+pub fn render_ascii(map: &Map, objects: &[Object], fov: Option<&FovMap>) -> String {
+    let shown = |x: i32, y: i32| fov.is_none() || map.get_tile(x, y).explored;
+    let mut rows: Vec<Vec<char>> = (0..map.height)
+        .map(|y| {
+            (0..map.width)
+                .map(|x| {
+                    let tile = map.get_tile(x, y);
+                    match (shown(x, y), tile.door_char(), tile.block_sight) {
+                        (false, _, _) => ' ',
+                        (true, Some(door), _) => door,
+                        (true, None, true) => '#',
+                        (true, None, false) => '.',
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // same rules as the game screen: blocking objects are drawn over the rest
+    let mut to_draw: Vec<_> = objects
+        .iter()
+        .filter(|o| {
+            let (x, y) = o.get_pos();
+            map.valid_point(x, y)
+                && fov.is_none_or(|fov| {
+                    fov.is_in_fov(o) || (o.always_visible && map.get_tile(x, y).explored)
+                })
+        })
+        .collect();
+    to_draw.sort_by_key(|o| o.blocks);
+    for object in to_draw {
+        let (x, y) = object.get_pos();
+        rows[y as usize][x as usize] = object.char;
+    }
+
+    let mut text = String::new();
+    for row in rows {
+        text.extend(row);
+        text.push('\n');
+    }
+    text
+}
+
+///
+/// Draws how much light is on each tile, from '0' for pitch black to '9' for fully lit.
+/// Given a FovMap, only tiles in view show their light: tiles the player only remembers
+/// are drawn as '-', as the light on them is no longer known, and unexplored ones are left blank.
+///
+pub fn render_light(map: &Map, fov: Option<&FovMap>) -> String {
+    let mut text = String::new();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let tile = map.get_tile(x, y);
+            text.push(match fov {
+                Some(_) if !tile.explored => ' ',
+                Some(fov) if !fov.is_visible(x, y) => '-',
+                _ => {
+                    let level = (tile.light.brightness() * 9.0).round() as u32;
+                    std::char::from_digit(level.min(9), 10).unwrap()
+                }
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colors;
+    use entities::light::Illumination;
+
+    fn room() -> Map {
+        Map::from_rows(&["#######", "#.....#", "#.....#", "#######"])
+    }
+
+    #[test]
+    fn shows_the_whole_level_without_a_fov() {
+        let objects = vec![
+            Object::new(1, 1, '@', "player", colors::WHITE, true),
+            Object::new(4, 2, '!', "potion", colors::WHITE, false),
+        ];
+        assert_eq!(
+            render_ascii(&room(), &objects, None),
+            "#######\n#@....#\n#...!.#\n#######\n"
+        );
+    }
+
+    #[test]
+    fn hides_what_the_player_has_not_seen() {
+        let mut map = room();
+        let mut fov = FovMap::new(7, 4);
+        for x in 0..4 {
+            for y in 0..4 {
+                fov.set_visible(x, y);
+            }
+        }
+        map.mark_explored(&fov);
+        let objects = vec![
+            Object::new(3, 2, 'o', "orc", colors::WHITE, true),
+            Object::new(3, 2, '%', "corpse", colors::WHITE, false),
+            Object::new(5, 1, 'T', "troll", colors::WHITE, true),
+        ];
+        assert_eq!(
+            render_ascii(&map, &objects, Some(&fov)),
+            "####   \n#...   \n#..o   \n####   \n"
+        );
+    }
+
+    #[test]
+    fn shows_the_light_on_every_tile_without_a_fov() {
+        let mut map = room();
+        map.map[1][1].light = Illumination::grey(1.0);
+        map.map[2][1].light = Illumination::grey(0.5);
+        // walls keep the ambient light they were made with, the unlit floor is pitch black
+        assert_eq!(
+            render_light(&map, None),
+            "2222222\n2950002\n2000002\n2222222\n"
+        );
+    }
+
+    #[test]
+    fn tells_tiles_in_view_from_remembered_ones() {
+        let mut map = room();
+        for x in 0..7 {
+            for y in 0..4 {
+                map.map[x][y].light = Illumination::grey(0.2);
+            }
+        }
+        map.map[1][1].light = Illumination::grey(1.0);
+        let mut fov = FovMap::new(7, 4);
+        for x in 0..4 {
+            for y in 0..4 {
+                fov.set_visible(x, y);
+            }
+        }
+        map.mark_explored(&fov);
+
+        // the player has since stepped back, and only sees the west end of the room
+        let mut fov = FovMap::new(7, 4);
+        for x in 0..2 {
+            for y in 0..4 {
+                fov.set_visible(x, y);
+            }
+        }
+        assert_eq!(
+            render_light(&map, Some(&fov)),
+            "22--   \n29--   \n22--   \n22--   \n"
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use tcod;
+use tcod::console::*;
+
+use colors::Color;
+use entities::object::Object;
+
+impl From<Color> for tcod::Color {
+    fn from(color: Color) -> Self {
+        tcod::Color {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+        }
+    }
+}
+
+//Puts the object's char on the console, in the given color (its own, tinted by the light on it)
+pub fn draw(object: &Object, color: Color, con: &mut dyn Console) {
+    let (x, y) = object.get_pos();
+    con.set_default_foreground(color.into());
+    con.put_char(x, y, object.char, BackgroundFlag::None);
+}
+
+pub fn clear(object: &Object, con: &mut dyn Console) {
+    let (x, y) = object.get_pos();
+    con.put_char(x, y, ' ', BackgroundFlag::None);
+}
@@ -0,0 +1,121 @@
+use tcod::colors;
+use tcod::console::*;
+
+///
+/// Shows a list of lettered options over the root console and waits for a key.
+/// Returns the index of the chosen option, if any.
+///
+pub fn menu<T: AsRef<str>>(
+    header: &str,
+    options: &[T],
+    width: i32,
+    root: &mut Root,
+) -> Option<usize> {
+    let window = menu_window(header, options, width, root);
+
+    // blit the contents of "window" to the root console, centered
+    let height = window.height();
+    let x = root.width() / 2 - width / 2;
+    let y = root.height() / 2 - height / 2;
+    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+
+    choose(options.len(), root)
+}
+
+///
+/// Same as menu, but drawn over con (the map) instead of straight onto the root console.
+/// con is then shown at the top left of the root console, with the menu on it.
+///
+pub fn menu_over<T: AsRef<str>>(
+    header: &str,
+    options: &[T],
+    width: i32,
+    con: &mut Offscreen,
+    root: &mut Root,
+) -> Option<usize> {
+    let window = menu_window(header, options, width, con);
+
+    // blit the contents of "window" to con, centered, and con to the root console
+    let height = window.height();
+    let x = con.width() / 2 - width / 2;
+    let y = con.height() / 2 - height / 2;
+    blit(&window, (0, 0), (width, height), con, (x, y), 1.0, 0.7);
+    let (con_width, con_height) = (con.width(), con.height());
+    blit(con, (0, 0), (con_width, con_height), root, (0, 0), 1.0, 1.0);
+
+    choose(options.len(), root)
+}
+
+//The menu's window: the header, wrapped to fit the width, followed by the lettered options
+fn menu_window<T: AsRef<str>, C: Console>(
+    header: &str,
+    options: &[T],
+    width: i32,
+    con: &C,
+) -> Offscreen {
+    assert!(
+        options.len() <= 26,
+        "Cannot have a menu with more than 26 options."
+    );
+
+    // calculate total height for the header (after auto-wrap) and one line per option
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        con.get_height_rect(0, 0, width, con.height(), header)
+    };
+    let height = options.len() as i32 + header_height;
+
+    // create an off-screen console that represents the menu's window
+    let mut window = Offscreen::new(width, height);
+
+    // print the header, with auto-wrap
+    window.set_default_foreground(colors::WHITE);
+    window.print_rect_ex(
+        0,
+        0,
+        width,
+        height,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        header,
+    );
+
+    // print all the options
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        window.print_ex(
+            0,
+            header_height + index as i32,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            text,
+        );
+    }
+
+    window
+}
+
+//Presents the root console to the player and waits for the key that picks an option
+fn choose(num_options: usize, root: &mut Root) -> Option<usize> {
+    root.flush();
+    let key = root.wait_for_keypress(true);
+
+    // convert the ASCII code to an index; if it corresponds to an option, return it
+    if key.printable.is_alphabetic() {
+        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
+        if index < num_options {
+            Some(index)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+pub fn msgbox(text: &str, width: i32, root: &mut Root) {
+    let options: &[&str] = &[];
+    menu(text, options, width, root);
+}
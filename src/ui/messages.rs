@@ -1,5 +1,6 @@
-use tcod::Color;
+use colors::Color;
 
+#[derive(Serialize, Deserialize)]
 pub struct Messages {
     messages: Vec<(String, Color)>,
 }
@@ -14,6 +15,10 @@ impl Messages {
         self.messages.push((message.into(), color));
     }
 
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
     /// Create a `DoubleEndedIterator` over the messages
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(String, Color)> {
         self.messages.iter()
@@ -1 +1,4 @@
+pub mod ascii;
+pub mod draw;
+pub mod menu;
 pub mod messages;